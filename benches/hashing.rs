@@ -0,0 +1,42 @@
+use asfald::{HashAlgorithm, Hasher};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::io::Write;
+
+// Size of the file hashed, big enough to not fit in the CPU caches
+const FILE_SIZE: usize = 64 * 1024 * 1024;
+
+fn bench_file_hashing(c: &mut Criterion) {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let chunk: Vec<u8> = (0..=255).cycle().take(1024 * 1024).collect();
+    for _ in 0..FILE_SIZE / chunk.len() {
+        file.write_all(&chunk).unwrap();
+    }
+    file.flush().unwrap();
+
+    let mut group = c.benchmark_group("compute_file_hash");
+    group.throughput(Throughput::Bytes(FILE_SIZE as u64));
+    group.sample_size(10);
+    for algorithm in [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake3,
+    ] {
+        group.bench_with_input(
+            BenchmarkId::new("buffered", &algorithm),
+            &algorithm,
+            |b, algorithm| b.iter(|| Hasher::compute_file_hash(file.path(), algorithm).unwrap()),
+        );
+        #[cfg(feature = "mmap")]
+        group.bench_with_input(
+            BenchmarkId::new("mmap", &algorithm),
+            &algorithm,
+            |b, algorithm| {
+                b.iter(|| Hasher::compute_file_hash_mmap(file.path(), algorithm).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_file_hashing);
+criterion_main!(benches);
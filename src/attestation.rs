@@ -0,0 +1,155 @@
+use crate::sigstore::VerifiedBundle;
+use serde::Deserialize;
+
+// Predicate type of the build provenance attestations of actions/attest-build-provenance
+pub const SLSA_PROVENANCE_PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+// OIDC issuer of the certificates of GitHub Actions workflows
+pub const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+// Requirements GitHub artifact attestations must satisfy, like the options of
+// gh attestation verify. By default, attestations must be SLSA build provenance, signed by
+// a workflow of the repository the file is downloaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationPolicy {
+    // owner/repo the file must have been built for, instead of the one it is downloaded from
+    pub repository: Option<String>,
+    // Workflow which must have built the file, as owner/repo/path, eg
+    // owner/repo/.github/workflows/release.yml. It may be a reusable workflow of another
+    // repository.
+    pub signer_workflow: Option<String>,
+    // Git ref the build must have been run for, eg refs/tags/v1.0.0
+    pub source_ref: Option<String>,
+    pub predicate_type: String,
+    pub issuer: String,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttestationPolicy {
+    pub fn new() -> Self {
+        Self {
+            repository: None,
+            signer_workflow: None,
+            source_ref: None,
+            predicate_type: SLSA_PROVENANCE_PREDICATE_TYPE.to_string(),
+            issuer: GITHUB_ACTIONS_ISSUER.to_string(),
+        }
+    }
+
+    pub fn with_repository(self, repository: String) -> Self {
+        Self {
+            repository: Some(repository),
+            ..self
+        }
+    }
+
+    pub fn with_signer_workflow(self, signer_workflow: String) -> Self {
+        Self {
+            signer_workflow: Some(signer_workflow),
+            ..self
+        }
+    }
+
+    pub fn with_source_ref(self, source_ref: String) -> Self {
+        Self {
+            source_ref: Some(source_ref),
+            ..self
+        }
+    }
+
+    pub fn with_predicate_type(self, predicate_type: String) -> Self {
+        Self {
+            predicate_type,
+            ..self
+        }
+    }
+
+    // Checks that the verified attestation of a file downloaded from owner/repo satisfies
+    // the policy, returning why it doesn't otherwise
+    pub fn check(
+        &self,
+        owner: &str,
+        repo: &str,
+        attestation: &VerifiedBundle,
+    ) -> std::result::Result<(), String> {
+        let Some(statement) = &attestation.statement else {
+            return Err("the bundle holds no in-toto statement".to_string());
+        };
+        if statement.predicate_type != self.predicate_type {
+            return Err(format!(
+                "the predicate type is {}, not {}",
+                statement.predicate_type, self.predicate_type
+            ));
+        }
+        if attestation.signer.issuer != self.issuer {
+            return Err(format!(
+                "the certificate was issued by {}, not {}",
+                attestation.signer.issuer, self.issuer
+            ));
+        }
+
+        // GitHub owner and repository names are case insensitive
+        let repository = match &self.repository {
+            Some(repository) => repository.clone(),
+            None => format!("{}/{}", owner, repo),
+        };
+        let build = &attestation.build;
+        let source_repository = build.source_repository_uri.as_deref().unwrap_or_default();
+        if !source_repository.eq_ignore_ascii_case(&format!("https://github.com/{}", repository)) {
+            return Err(format!(
+                "the file was built for {}, not {}",
+                source_repository, repository
+            ));
+        }
+        // Without a workflow required, any workflow of the repository may have signed
+        let signer = build
+            .build_signer_uri
+            .as_deref()
+            .unwrap_or(&attestation.signer.identity);
+        let signer_prefix = match &self.signer_workflow {
+            Some(workflow) => format!("https://github.com/{}@", workflow),
+            None => format!("https://github.com/{}/", repository),
+        };
+        let signed_by_workflow = signer
+            .get(..signer_prefix.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&signer_prefix));
+        if !signed_by_workflow {
+            return Err(format!(
+                "the file was built by {}, not {}",
+                signer,
+                self.signer_workflow.as_deref().unwrap_or(&repository)
+            ));
+        }
+        if let Some(source_ref) = &self.source_ref {
+            if build.source_repository_ref.as_ref() != Some(source_ref) {
+                return Err(format!(
+                    "the file was built from {}, not {}",
+                    build
+                        .source_repository_ref
+                        .as_deref()
+                        .unwrap_or("an unknown ref"),
+                    source_ref
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A page of the response of the attestations endpoint of the GitHub API
+#[derive(Deserialize)]
+pub(crate) struct AttestationsResponse {
+    #[serde(default)]
+    pub(crate) attestations: Vec<AttestationJson>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AttestationJson {
+    pub(crate) bundle: Option<serde_json::Value>,
+    // Large bundles are only available from this URL, compressed with snappy
+    pub(crate) bundle_url: Option<String>,
+}
@@ -0,0 +1,177 @@
+use crate::hasher::HashAlgorithm;
+
+// Names (lowercased) of files published in releases to list checksums of all assets.
+const CHECKSUMS_FILE_NAMES: [&str; 13] = [
+    "checksums.txt",
+    "checksums",
+    "checksums.sha256",
+    "checksums.sha512",
+    "sha256sums",
+    "sha256sums.txt",
+    "sha256sum.txt",
+    "sha256.txt",
+    "sha2-256sums",
+    "sha512sums",
+    "sha512sums.txt",
+    "b3sums",
+    "b3sums.txt",
+];
+
+// Suffixes (lowercased) of files holding checksums of all assets, eg lazygit_0.44.0_checksums.txt
+const CHECKSUMS_FILE_SUFFIXES: [&str; 6] = [
+    "_checksums.txt",
+    "-checksums.txt",
+    "_sha256sums.txt",
+    "-sha256sums.txt",
+    "_sha512sums.txt",
+    "-sha512sums.txt",
+];
+
+// Extensions appended to an asset name to publish that asset's checksum, eg file.tar.gz.sha256
+const ASSET_CHECKSUM_EXTENSIONS: [&str; 6] = [
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sha512sum",
+    ".b3",
+    ".blake3",
+];
+
+// Markers in checksums file names identifying the algorithm used, for algorithms that cannot
+// be guessed from the hash length.
+const ALGORITHM_HINTS: [(&str, HashAlgorithm); 2] = [
+    ("b3", HashAlgorithm::Blake3),
+    ("blake3", HashAlgorithm::Blake3),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    pub algorithm: HashAlgorithm,
+    pub hash: String,
+    // Empty for files holding only a hash value
+    pub filename: String,
+}
+
+/// Returns true if `name` is a checksums file covering all assets of a release.
+pub fn is_checksums_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    CHECKSUMS_FILE_NAMES.contains(&name.as_str())
+        || CHECKSUMS_FILE_SUFFIXES.iter().any(|s| name.ends_with(s))
+}
+
+/// Returns true if `name` is the checksum file dedicated to the asset `filename`.
+pub fn is_asset_checksum_file(name: &str, filename: &str) -> bool {
+    ASSET_CHECKSUM_EXTENSIONS
+        .iter()
+        .any(|ext| name == format!("{}{}", filename, ext))
+}
+
+/// Returns true if `name` has an extension used for files holding the checksum of an asset.
+pub fn has_checksum_extension(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ASSET_CHECKSUM_EXTENSIONS
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// Parses checksums files as generated by sha256sum (`<hash>  <file>`, possibly with
+/// a `*` before the file name for binary mode) or by BSD tools (`SHA256 (<file>) = <hash>`).
+/// Lines with only a hash are accepted, for files holding the checksum of a single asset.
+/// Lines that cannot be parsed or use an unsupported algorithm are ignored.
+pub fn parse_checksums(content: &str) -> Vec<ChecksumEntry> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| parse_bsd_line(l).or_else(|| parse_gnu_line(l)))
+        .collect()
+}
+
+/// Looks for the checksum of `filename` in the content of a checksums file.
+/// Files in subdirectories are matched on their base name, unless several of them have that
+/// name with different hashes, eg linux/tool.tar.gz and darwin/tool.tar.gz.
+pub fn find_checksum(content: &str, filename: &str) -> Option<ChecksumEntry> {
+    let entries = parse_checksums(content);
+    if let Some(entry) = entries
+        .iter()
+        .find(|e| e.filename.trim_start_matches("./") == filename)
+    {
+        return Some(entry.clone());
+    }
+    let mut matches = entries
+        .into_iter()
+        .filter(|e| e.filename.rsplit('/').next() == Some(filename));
+    let first = matches.next()?;
+    matches.all(|e| e.hash == first.hash).then_some(first)
+}
+
+/// Looks for the checksum of `filename` in the content of the checksums file named
+/// `checksums_file`, accepting a lone hash if that file is dedicated to `filename`.
+pub fn lookup(checksums_file: &str, content: &str, filename: &str) -> Option<ChecksumEntry> {
+    let bare_hash = is_asset_checksum_file(checksums_file, filename)
+        .then(|| {
+            parse_checksums(content)
+                .into_iter()
+                .find(|e| e.filename.is_empty())
+        })
+        .flatten();
+    bare_hash
+        .or_else(|| find_checksum(content, filename))
+        .map(|entry| apply_algorithm_hint(checksums_file, entry))
+}
+
+// Hashes in eg B3SUMS have the length of Sha256 hashes, but are Blake3 hashes.
+fn apply_algorithm_hint(checksums_file: &str, entry: ChecksumEntry) -> ChecksumEntry {
+    let name = checksums_file.to_lowercase();
+    let hint = ALGORITHM_HINTS.iter().find(|(marker, _)| {
+        name.split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|part| part == *marker || part == format!("{}sums", marker))
+    });
+    match hint {
+        Some((_, algorithm)) if algorithm.hex_length() == entry.hash.len() => ChecksumEntry {
+            algorithm: algorithm.clone(),
+            ..entry
+        },
+        _ => entry,
+    }
+}
+
+// SHA256 (file.tar.gz) = 6ae8a755...
+fn parse_bsd_line(line: &str) -> Option<ChecksumEntry> {
+    let (algorithm, rest) = line.split_once(" (")?;
+    let (filename, hash) = rest.rsplit_once(") = ")?;
+    let algorithm: HashAlgorithm = algorithm.trim().parse().ok()?;
+    let hash = normalise_hash(hash, &algorithm)?;
+    Some(ChecksumEntry {
+        algorithm,
+        hash,
+        filename: filename.to_string(),
+    })
+}
+
+// 6ae8a755...  file.tar.gz
+// 6ae8a755... *file.tar.gz
+// 6ae8a755...
+fn parse_gnu_line(line: &str) -> Option<ChecksumEntry> {
+    let (hash, filename) = match line.split_once(char::is_whitespace) {
+        Some((hash, filename)) => (hash, filename.trim_start()),
+        None => (line, ""),
+    };
+    let algorithm = HashAlgorithm::from_hex_length(hash.len())?;
+    let hash = normalise_hash(hash, &algorithm)?;
+    Some(ChecksumEntry {
+        algorithm,
+        hash,
+        filename: filename.trim_start_matches('*').to_string(),
+    })
+}
+
+fn normalise_hash(hash: &str, algorithm: &HashAlgorithm) -> Option<String> {
+    let hash = hash.trim().to_lowercase();
+    if Some(algorithm) != HashAlgorithm::from_hex_length(hash.len()).as_ref()
+        || !hash.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    Some(hash)
+}
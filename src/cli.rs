@@ -1,5 +1,15 @@
-use clap::Parser;
+use crate::{
+    attestation::SLSA_PROVENANCE_PREDICATE_TYPE,
+    downloader::DEFAULT_CONCURRENCY,
+    hasher::{HashAlgorithm, Hasher},
+    mirror::DEFAULT_MIRROR_URL,
+    policy::VerificationPolicy,
+    selector::AssetSelector,
+    signature::{MinisignKey, DEFAULT_SIGNATURE_NAMESPACE},
+};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
+use std::str::FromStr;
 use url::Url;
 
 #[derive(Parser, Debug)]
@@ -7,13 +17,193 @@ use url::Url;
     name = "asfald",
     about = "Downloads files from GitHub releases with hash verification",
     version,
-    author
+    author,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct Cli {
-    /// Output file path
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Downloading is the default command, so that `asfald <URL>` keeps working
+    #[command(flatten)]
+    pub download: DownloadArgs,
+}
+
+impl Cli {
+    // The command to run, defaulting to a download
+    pub fn command(self) -> Command {
+        self.command
+            .unwrap_or(Command::Download(Box::new(self.download)))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Download a file and verify its checksum (default command)
+    Download(Box<DownloadArgs>),
+    /// Verify the checksum of an already downloaded file
+    Verify(Box<VerifyArgs>),
+    /// List the assets of a release with their digests
+    List(ListArgs),
+    /// Download and verify a binary, and install it in a bin directory
+    Install(Box<InstallArgs>),
+    /// List the binaries installed with the install command
+    Installed(InstalledArgs),
+    /// Download and verify all files listed in a TOML manifest
+    Batch(Box<BatchArgs>),
+}
+
+impl Command {
+    // Whether the command must not output anything, even its errors
+    pub fn quiet(&self) -> bool {
+        match self {
+            Command::Download(args) => args.verification.quiet,
+            Command::Verify(args) => args.verification.quiet,
+            Command::Install(args) => args.verification.quiet,
+            Command::Batch(args) => args.verification.quiet,
+            Command::List(_) | Command::Installed(_) => false,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct DownloadArgs {
+    /// Output file path, use - to write the file to stdout
     #[arg(short, long, value_name = "PATH")]
     pub output: Option<PathBuf>,
 
+    /// Always restart downloads from scratch instead of resuming a partial .part file
+    #[arg(long)]
+    pub no_resume: bool,
+
+    /// Overwrite the output file if it already exists
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Extract the downloaded archive (.tar.gz, .tar.xz, .zip or .gz) in this directory once
+    /// its checksum has been verified
+    #[arg(long, value_name = "DIR")]
+    pub extract: Option<PathBuf>,
+
+    /// Only extract this member of the archive, designated by its path in the archive or its
+    /// file name, directly in the extraction directory
+    #[arg(long, value_name = "NAME", requires = "extract")]
+    pub member: Option<String>,
+
+    /// Asset to download from the repository: its name, a glob pattern (eg
+    /// lazygit_*_Linux_x86_64.tar.gz or glob:<pattern>), regex:<regex>, or auto to select the
+    /// asset built for this platform. Defaults to auto.
+    #[arg(long, value_name = "SELECTOR")]
+    pub asset: Option<AssetSelector>,
+
+    #[command(flatten)]
+    pub verification: VerificationArgs,
+
+    /// URL of the file in a release, or repository as owner/repo optionally followed by @
+    /// and a tag, latest, or a semver requirement, from which --asset is downloaded
+    #[arg(required = true, value_name = "URL|OWNER/REPO[@RELEASE]")]
+    pub target: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub verification: VerificationArgs,
+
+    /// URL the file was downloaded from
+    pub url: Url,
+
+    /// Path of the downloaded file
+    pub file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// GitHub API token (can also be set via GITHUB_API_KEY env var)
+    #[arg(short, long, env = "GITHUB_API_KEY")]
+    pub token: Option<String>,
+
+    /// Output the release as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Repository as owner/repo, optionally followed by @ and a tag, latest, or a semver
+    /// requirement like ^1.4. Defaults to the latest release.
+    #[arg(value_name = "OWNER/REPO[@RELEASE]")]
+    pub repo: String,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallArgs {
+    /// Directory where the binary is installed [default: ~/.local/bin]
+    #[arg(long, value_name = "DIR")]
+    pub bin_dir: Option<PathBuf>,
+
+    /// Name of the binary in the archive, by path or file name [default: the installed name]
+    #[arg(long, value_name = "NAME")]
+    pub binary: Option<String>,
+
+    /// Name of the installed binary, without any path [default: the repository name]
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+
+    /// Asset to install from the repository, see the download command. Defaults to auto.
+    #[arg(long, value_name = "SELECTOR")]
+    pub asset: Option<AssetSelector>,
+
+    #[command(flatten)]
+    pub verification: VerificationArgs,
+
+    /// URL of the asset in a release, or repository as owner/repo optionally followed by @
+    /// and a tag, latest, or a semver requirement
+    #[arg(value_name = "URL|OWNER/REPO[@RELEASE]")]
+    pub target: String,
+}
+
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    /// Always restart downloads from scratch instead of resuming a partial .part file
+    #[arg(long)]
+    pub no_resume: bool,
+
+    /// Overwrite output files if they already exist
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Maximum number of files downloaded in parallel
+    #[arg(short, long, value_name = "N", default_value_t = DEFAULT_CONCURRENCY)]
+    pub jobs: usize,
+
+    /// Lockfile recording the assets downloaded and their digests [default: the manifest
+    /// path with the .lock extension]
+    #[arg(long, value_name = "PATH")]
+    pub lockfile: Option<PathBuf>,
+
+    /// Download the assets recorded in the lockfile, verified against their locked digests
+    /// in addition to the --policy, failing if the manifest doesn't resolve to the same
+    /// assets anymore
+    #[arg(long)]
+    pub locked: bool,
+
+    #[command(flatten)]
+    pub verification: VerificationArgs,
+
+    /// Manifest listing the files to download
+    #[arg(value_name = "MANIFEST")]
+    pub manifest: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct InstalledArgs {
+    /// Output the installed binaries as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+// Options shared by all commands verifying checksums
+#[derive(Args, Debug)]
+pub struct VerificationArgs {
     /// GitHub API token (can also be set via GITHUB_API_KEY env var)
     #[arg(short, long, env = "GITHUB_API_KEY")]
     pub token: Option<String>,
@@ -26,5 +216,105 @@ pub struct Cli {
     #[arg(short, long)]
     pub quiet: bool,
 
-    pub url: Url,
+    /// Cross-check the checksum with the asfaload checksums mirror, which must have a
+    /// checksum for the file
+    #[arg(long)]
+    pub mirror: bool,
+
+    /// Base URL of the checksums mirror
+    #[arg(long, value_name = "URL", env = "ASFALD_MIRROR_URL", default_value = DEFAULT_MIRROR_URL)]
+    pub mirror_url: Url,
+
+    /// How checksums from the different sources are combined: any (first source found, and
+    /// the mirror if requested), all (all sources found must match), at-least:<N> or
+    /// require:<pinned|asset-digest|release-checksums|mirror>
+    #[arg(long, value_name = "POLICY", default_value_t = VerificationPolicy::default())]
+    pub policy: VerificationPolicy,
+
+    /// Accept checksums computed with weak algorithms (sha1, md5)
+    #[arg(long)]
+    pub allow_weak_algorithms: bool,
+
+    /// Also compute the digest of the file with this algorithm (sha256, sha384, sha512,
+    /// blake3, sha1, md5), reported in verbose mode. Can be repeated.
+    #[arg(long = "digest", value_name = "ALGORITHM")]
+    pub digests: Vec<HashAlgorithm>,
+
+    /// Digest the file must have, as <algorithm>:<hex>, eg sha256:<hex>, whatever the other
+    /// checksum sources say. Can be repeated for several algorithms.
+    #[arg(long = "expect", value_name = "DIGEST", value_parser = Hasher::parse_digest)]
+    pub expected: Vec<(HashAlgorithm, String)>,
+
+    /// File listing the SSH keys trusted to sign checksums files and assets, in the format
+    /// of ssh-keygen's allowed signers. Checksums files of releases are then only used if
+    /// signed (<file>.sig) by one of these keys.
+    #[arg(long, value_name = "FILE", env = "ASFALD_ALLOWED_SIGNERS")]
+    pub allowed_signers: Option<PathBuf>,
+
+    /// Namespace the signatures must have been made for (ssh-keygen -Y sign -n)
+    #[arg(long, value_name = "NAMESPACE", default_value = DEFAULT_SIGNATURE_NAMESPACE)]
+    pub signature_namespace: String,
+
+    /// Minisign public key (base64) the asset must be signed with: the download fails
+    /// unless the release has a valid <asset>.minisig signature made with it. The file: field
+    /// of its trusted comment, if any, must be the asset name, so renamed assets are rejected.
+    #[arg(long, value_name = "KEY", env = "ASFALD_MINISIGN_KEY", value_parser = MinisignKey::from_str)]
+    pub minisign_key: Option<MinisignKey>,
+
+    /// OpenPGP keyring (gpg --export [--armor]) whose keys are trusted to sign files:
+    /// checksums files are only used if signed (<file>.asc, .sig or .gpg), and the asset
+    /// must be signed itself or through the checksums file.
+    #[arg(long, value_name = "FILE", env = "ASFALD_KEYRING")]
+    pub keyring: Option<PathBuf>,
+
+    /// Sigstore trust root, either a trusted_root.json file or a PEM file with the Fulcio CA
+    /// certificates and the Rekor public key: the download fails unless the release has a
+    /// valid Sigstore bundle of the asset (<asset>.sigstore.json, .sigstore or .bundle),
+    /// verified offline. With --require-attestation, bundles are only required if a
+    /// certificate identity is given.
+    #[arg(long, value_name = "FILE", env = "ASFALD_SIGSTORE_TRUST_ROOT")]
+    pub sigstore_trust_root: Option<PathBuf>,
+
+    /// Regular expression the identity (email or URI) of the certificate of Sigstore
+    /// bundles must match
+    #[arg(
+        long,
+        value_name = "REGEX",
+        requires = "certificate_oidc_issuer_regexp"
+    )]
+    pub certificate_identity_regexp: Option<String>,
+
+    /// Regular expression the OIDC issuer of the certificate of Sigstore bundles must
+    /// match, eg ^https://token\.actions\.githubusercontent\.com$
+    #[arg(long, value_name = "REGEX", requires = "certificate_identity_regexp")]
+    pub certificate_oidc_issuer_regexp: Option<String>,
+
+    /// Require a GitHub artifact attestation of the asset (SLSA build provenance by
+    /// default), signed by a workflow of its repository and verified against the Sigstore
+    /// trust root
+    #[arg(long, requires = "sigstore_trust_root")]
+    pub require_attestation: bool,
+
+    /// Repository (owner/repo) the asset must have been built for, instead of the one it
+    /// is downloaded from
+    #[arg(long, value_name = "OWNER/REPO", requires = "require_attestation")]
+    pub attestation_repo: Option<String>,
+
+    /// Workflow which must have built the asset, as owner/repo/path, eg
+    /// owner/repo/.github/workflows/release.yml
+    #[arg(long, value_name = "WORKFLOW", requires = "require_attestation")]
+    pub attestation_signer_workflow: Option<String>,
+
+    /// Git ref the asset must have been built from, eg refs/tags/v1.0.0
+    #[arg(long, value_name = "REF", requires = "require_attestation")]
+    pub attestation_source_ref: Option<String>,
+
+    /// Predicate type of the attestation
+    #[arg(
+        long,
+        value_name = "URI",
+        default_value = SLSA_PROVENANCE_PREDICATE_TYPE,
+        requires = "require_attestation"
+    )]
+    pub attestation_predicate_type: String,
 }
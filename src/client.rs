@@ -1,20 +1,99 @@
-use crate::{Error, Result};
-use serde::Deserialize;
+use crate::{
+    attestation::{AttestationJson, AttestationPolicy, AttestationsResponse},
+    hasher::HashAlgorithm,
+    selector::AssetSelector,
+    sigstore::{SigstoreBundle, SigstoreTrustRoot, VerifiedBundle},
+    Error, Result,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{env, str::FromStr};
 use url::Url;
 
-#[derive(Debug, Deserialize)]
+// Number of releases requested per page when listing releases
+const RELEASES_PER_PAGE: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitHubRelease {
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
     pub assets: Vec<GitHubAsset>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitHubAsset {
     pub name: String,
-    pub digest: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub content_type: String,
+    // Not available for assets published before GitHub started computing digests
+    #[serde(default)]
+    pub digest: Option<String>,
     pub browser_download_url: String,
 }
 
+// Designates a release of a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReleaseSpec {
+    Tag(String),
+    // The release marked as latest on GitHub
+    Latest,
+    // The release with the highest version satisfying the requirement, prereleases excluded.
+    // The version is taken from the tag, ignoring a leading v.
+    Requirement(semver::VersionReq),
+}
+
+impl FromStr for ReleaseSpec {
+    type Err = Error;
+
+    // "latest", a semver requirement starting with an operator (eg ^1.4, >=2, ~0.3.1, *),
+    // or a tag
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "latest" {
+            Ok(ReleaseSpec::Latest)
+        } else if s.starts_with(['^', '~', '=', '>', '<', '*']) {
+            semver::VersionReq::parse(s)
+                .map(ReleaseSpec::Requirement)
+                .map_err(|e| Error::InvalidVersionRequirement(format!("{}: {}", s, e)))
+        } else {
+            Ok(ReleaseSpec::Tag(s.to_string()))
+        }
+    }
+}
+
+impl std::fmt::Display for ReleaseSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseSpec::Tag(tag) => write!(f, "{}", tag),
+            ReleaseSpec::Latest => write!(f, "latest"),
+            ReleaseSpec::Requirement(req) => write!(f, "{}", req),
+        }
+    }
+}
+
+// A release asset, as designated by a download URL or by a repository and an asset selector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetRef {
+    pub owner: String,
+    pub repo: String,
+    pub release: ReleaseSpec,
+    pub asset: AssetSelector,
+}
+
+impl std::fmt::Display for AssetRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}@{} {}",
+            self.owner, self.repo, self.release, self.asset
+        )
+    }
+}
+
 pub struct GitHubClient {
     client: reqwest::Client,
     api_key: Option<String>,
@@ -44,15 +123,196 @@ impl GitHubClient {
         }
     }
 
+    // Overrides the API key read from the GITHUB_API_KEY environment variable
+    pub fn with_api_key(self, api_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.or(self.api_key),
+            ..self
+        }
+    }
+
     pub async fn get_release(&self, owner: &str, repo: &str, tag: &str) -> Result<GitHubRelease> {
-        let url = format!(
-            "{}repos/{}/{}/releases/tags/{}",
-            self.api_url, owner, repo, tag
-        );
+        self.get(&format!("repos/{}/{}/releases/tags/{}", owner, repo, tag))
+            .await
+    }
+
+    pub async fn get_latest_release(&self, owner: &str, repo: &str) -> Result<GitHubRelease> {
+        self.get(&format!("repos/{}/{}/releases/latest", owner, repo))
+            .await
+    }
+
+    pub async fn find_release(
+        &self,
+        owner: &str,
+        repo: &str,
+        release: &ReleaseSpec,
+    ) -> Result<GitHubRelease> {
+        match release {
+            ReleaseSpec::Tag(tag) => self.get_release(owner, repo, tag).await,
+            ReleaseSpec::Latest => self.get_latest_release(owner, repo).await,
+            ReleaseSpec::Requirement(req) => {
+                self.get_release_matching(owner, repo, req, false).await
+            }
+        }
+    }
+
+    // Returns the release with the highest version matching req, going through all pages
+    // of releases. Drafts are always ignored, prereleases only if include_prereleases is false.
+    pub async fn get_release_matching(
+        &self,
+        owner: &str,
+        repo: &str,
+        req: &semver::VersionReq,
+        include_prereleases: bool,
+    ) -> Result<GitHubRelease> {
+        let mut best: Option<(semver::Version, GitHubRelease)> = None;
+        for page in 1.. {
+            let releases: Vec<GitHubRelease> = self
+                .get(&format!(
+                    "repos/{}/{}/releases?per_page={}&page={}",
+                    owner, repo, RELEASES_PER_PAGE, page
+                ))
+                .await?;
+            let last_page = releases.len() < RELEASES_PER_PAGE;
+
+            for release in releases {
+                if release.draft || (release.prerelease && !include_prereleases) {
+                    continue;
+                }
+                let Some(version) = Self::tag_version(&release.tag_name) else {
+                    continue;
+                };
+                // Prerelease versions only match requirements mentioning a prerelease
+                if !include_prereleases && !version.pre.is_empty() {
+                    continue;
+                }
+                if req.matches(&version) && best.as_ref().is_none_or(|(v, _)| version > *v) {
+                    best = Some((version, release));
+                }
+            }
+
+            if last_page {
+                break;
+            }
+        }
+
+        best.map(|(_, release)| release).ok_or_else(|| {
+            Error::ReleaseNotFound(format!("no release of {}/{} matches {}", owner, repo, req))
+        })
+    }
+
+    // The Sigstore bundles of the artifact attestations published by the repository for
+    // the file with this sha256 digest, going through all pages of attestations. Bundles
+    // which cannot be fetched or parsed are returned as errors.
+    pub async fn get_attestations(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha256: &str,
+    ) -> Result<Vec<Result<SigstoreBundle>>> {
+        let mut bundles = vec![];
+        let mut url = Some(format!(
+            "{}repos/{}/{}/attestations/sha256:{}",
+            self.api_url, owner, repo, sha256
+        ));
+        while let Some(page_url) = url {
+            let response = self.send_url(&page_url).await?;
+            // The API answers with a 404 when there is no attestation
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                break;
+            }
+            url = self.next_page(&response)?;
+            let page: AttestationsResponse = Self::parse_response(response).await?;
+            for attestation in page.attestations {
+                bundles.push(self.attestation_bundle(attestation).await);
+            }
+        }
+        Ok(bundles)
+    }
+
+    // The bundle of an attestation, fetched from its URL when it is not inline
+    async fn attestation_bundle(&self, attestation: AttestationJson) -> Result<SigstoreBundle> {
+        match (attestation.bundle, attestation.bundle_url) {
+            (Some(bundle), _) => bundle.to_string().parse(),
+            (None, Some(url)) => {
+                let response = self.file_request(&url).send().await?.error_for_status()?;
+                let bundle = snap::raw::Decoder::new()
+                    .decompress_vec(&response.bytes().await?)
+                    .map_err(|e| Error::InvalidSignature(format!("invalid bundle: {}", e)))?;
+                String::from_utf8_lossy(&bundle).parse()
+            }
+            (None, None) => Err(Error::InvalidSignature(
+                "the attestation has no bundle".to_string(),
+            )),
+        }
+    }
+
+    // Fetches the attestations of the file with this sha256 digest, and returns the first
+    // one verified against the trust root and satisfying the policy
+    pub async fn verify_attestations(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha256: &str,
+        trust_root: &SigstoreTrustRoot,
+        policy: &AttestationPolicy,
+    ) -> Result<VerifiedBundle> {
+        let repository = policy
+            .repository
+            .clone()
+            .unwrap_or(format!("{}/{}", owner, repo));
+        let bundles = self.get_attestations(owner, repo, sha256).await?;
+        if bundles.is_empty() {
+            return Err(Error::SignatureNotFound(format!(
+                "{} has no attestation for sha256:{}",
+                repository, sha256
+            )));
+        }
+        let digests = [(HashAlgorithm::Sha256, sha256.to_string())];
+        let mut reasons = vec![];
+        for (i, bundle) in bundles.into_iter().enumerate() {
+            let verified = bundle
+                .and_then(|bundle| bundle.verify(trust_root, &digests))
+                .and_then(|verified| {
+                    policy
+                        .check(owner, repo, &verified)
+                        .map(|_| verified)
+                        .map_err(Error::SignatureVerificationFailed)
+                });
+            match verified {
+                Ok(verified) => return Ok(verified),
+                Err(Error::SignatureVerificationFailed(reason)) => {
+                    reasons.push(format!("attestation {}: {}", i + 1, reason))
+                }
+                Err(e) => reasons.push(format!("attestation {}: {}", i + 1, e)),
+            }
+        }
+        Err(Error::SignatureVerificationFailed(format!(
+            "no attestation of sha256:{} satisfies the policy: {}",
+            sha256,
+            reasons.join("; ")
+        )))
+    }
+
+    // Extracts the version from tags like v1.2.3 or 1.2.3
+    fn tag_version(tag: &str) -> Option<semver::Version> {
+        semver::Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+    }
+
+    // Sends a GET request to the API endpoint at path, relative to the API base url
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Self::parse_response(self.send(path).await?).await
+    }
 
+    async fn send(&self, path: &str) -> Result<reqwest::Response> {
+        self.send_url(&format!("{}{}", self.api_url, path)).await
+    }
+
+    // Sends a GET request to an API URL, such as the next page of a paginated response
+    async fn send_url(&self, url: &str) -> Result<reqwest::Response> {
         let mut request = self
             .client
-            .get(&url)
+            .get(url)
             .header("Accept", "application/vnd.github+json")
             .header("User-Agent", "Asfaload-downloader")
             .header("X-GitHub-Api-Version", "2022-11-28");
@@ -61,8 +321,35 @@ impl GitHubClient {
             request = request.header("Authorization", format!("Bearer {}", key));
         }
 
-        let response = request.send().await?;
+        Ok(request.send().await?)
+    }
 
+    // The URL of the next page of a paginated response, from its Link header. It must be
+    // an API URL, as the API key is sent with the request.
+    fn next_page(&self, response: &reqwest::Response) -> Result<Option<String>> {
+        let Some(links) = response.headers().get(reqwest::header::LINK) else {
+            return Ok(None);
+        };
+        let next = links.to_str().ok().and_then(|links| {
+            links.split(',').find_map(|link| {
+                let (url, params) = link.split_once(';')?;
+                params
+                    .split(';')
+                    .any(|param| param.trim() == r#"rel="next""#)
+                    .then(|| url.trim().trim_start_matches('<').trim_end_matches('>'))
+            })
+        });
+        match next {
+            Some(url) if !url.starts_with(self.api_url.as_str()) => Err(Error::GitHubApiError(
+                format!("the next page {} is not an API URL", url),
+            )),
+            next => Ok(next.map(str::to_string)),
+        }
+    }
+
+    // Parses the JSON body of the response, failing with the API's message if the request
+    // failed
+    async fn parse_response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
         if !response.status().is_success() {
             let status = response.status();
             let message = response
@@ -72,8 +359,96 @@ impl GitHubClient {
             return Err(Error::GitHubApiError(format!("{}: {}", status, message)));
         }
 
-        let release: GitHubRelease = response.json().await?;
-        Ok(release)
+        Ok(response.json().await?)
+    }
+
+    // Starts a GET request for a file, like a release asset. The API key is only sent to
+    // GitHub, it is dropped when redirected to another host.
+    pub fn file_request(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .get(url)
+            .header("User-Agent", "Asfaload-downloader");
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string));
+        match (&self.api_key, host) {
+            (Some(key), Some(host))
+                if host == "github.com" || Some(host.as_str()) == self.api_url.host_str() =>
+            {
+                request.header("Authorization", format!("Bearer {}", key))
+            }
+            _ => request,
+        }
+    }
+
+    // Retrieves the content of a small text asset, like a checksums file
+    pub async fn get_asset_content(&self, asset: &GitHubAsset) -> Result<String> {
+        let response = self
+            .file_request(&asset.browser_download_url)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.text().await?)
+    }
+
+    // Like get_asset_content, for binary files such as OpenPGP signatures
+    pub async fn get_asset_bytes(&self, asset: &GitHubAsset) -> Result<Vec<u8>> {
+        let response = self
+            .file_request(&asset.browser_download_url)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    // Parses a repository reference of the form owner/repo, optionally followed by @ and
+    // a release spec (tag, latest or semver requirement). Defaults to the latest release.
+    pub fn parse_repo(repo: &str) -> Result<(String, String, ReleaseSpec)> {
+        let (repo, release) = match repo.split_once('@') {
+            Some((repo, spec)) if !spec.is_empty() => (repo, spec.parse()?),
+            Some(_) => return Err(Error::InvalidRepoFormat(repo.to_string())),
+            None => (repo, ReleaseSpec::Latest),
+        };
+        match repo.split('/').collect::<Vec<_>>()[..] {
+            [owner, name] if !owner.is_empty() && !name.is_empty() => {
+                Ok((owner.to_string(), name.to_string(), release))
+            }
+            _ => Err(Error::InvalidRepoFormat(repo.to_string())),
+        }
+    }
+
+    // Parses release download URLs, either for a given tag
+    // (https://github.com/owner/repo/releases/download/v1.0.0/file.tar.gz)
+    // or for the latest release (https://github.com/owner/repo/releases/latest/download/file.tar.gz)
+    pub fn parse_release_url(url: &str) -> Result<AssetRef> {
+        let parsed_url =
+            url::Url::parse(url).map_err(|_| Error::InvalidUrlFormat(url.to_string()))?;
+        let path_parts: Vec<&str> = parsed_url
+            .path_segments()
+            .ok_or_else(|| Error::InvalidUrlFormat(url.to_string()))?
+            .collect();
+
+        if path_parts.len() >= 6
+            && path_parts[2] == "releases"
+            && path_parts[3] == "latest"
+            && path_parts[4] == "download"
+        {
+            return Ok(AssetRef {
+                owner: path_parts[0].to_string(),
+                repo: path_parts[1].to_string(),
+                release: ReleaseSpec::Latest,
+                asset: AssetSelector::Name(path_parts[5..].join("/")),
+            });
+        }
+
+        let (owner, repo, tag, asset) = Self::parse_github_url(url)?;
+        Ok(AssetRef {
+            owner,
+            repo,
+            release: ReleaseSpec::Tag(tag),
+            asset: AssetSelector::Name(asset),
+        })
     }
 
     pub fn parse_github_url(url: &str) -> Result<(String, String, String, String)> {
@@ -1,18 +1,85 @@
 use crate::{
-    client::GitHubClient,
-    hasher::{HashAlgorithm, Hasher},
+    attestation::AttestationPolicy,
+    checksums,
+    client::{AssetRef, GitHubAsset, GitHubClient, GitHubRelease, ReleaseSpec},
+    extract::Extractor,
+    hasher::{HashAlgorithm, Hasher, MultiHasher},
+    mirror::ChecksumsMirror,
+    openpgp::{Keyring, PgpSignature, PgpVerification, OPENPGP_SIGNATURE_EXTENSIONS},
+    policy::{ChecksumSource, SourceReport, SourceStatus, VerificationPolicy},
+    signature::{
+        AllowedSigners, MinisignKey, MinisignSignature, MinisignVerification, SshSignature,
+        VerifiedSignature, DEFAULT_SIGNATURE_NAMESPACE, MINISIGN_SIGNATURE_EXTENSION,
+        SSH_SIGNATURE_EXTENSION,
+    },
+    sigstore::{SigstoreBundle, SigstoreIdentity, SigstoreTrustRoot, SIGSTORE_BUNDLE_EXTENSIONS},
     Error, Result,
 };
-use futures::TryStreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use sha2::{Digest, Sha256};
+use futures::{StreamExt, TryStreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::{
+    header::{CONTENT_RANGE, RANGE},
+    StatusCode,
+};
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::OnceCell;
+
+// Number of files downloaded in parallel by default by download_all
+pub const DEFAULT_CONCURRENCY: usize = 4;
 
 pub struct Downloader {
     pub client: GitHubClient,
     pub progress_init: Box<dyn Fn(u64) -> ProgressBar>,
+    // When true, an interrupted download left in a .part file is continued with a Range request
+    pub resume: bool,
+    // When false, downloads to an existing path fail with Error::FileExists
+    pub overwrite: bool,
+    // When set, checksums are cross-checked with the asfaload checksums mirror
+    pub mirror: Option<ChecksumsMirror>,
+    // How checksums from the different sources are combined
+    pub policy: VerificationPolicy,
+    // When false, checksums using weak algorithms (eg sha1, md5) are ignored
+    pub allow_weak_algorithms: bool,
+    // Algorithms for which a digest is computed, in addition to those needed for verification
+    pub algorithms: Vec<HashAlgorithm>,
+    // When set, downloaded archives are extracted once verified
+    pub extractor: Option<Extractor>,
+    // Maximum number of files downloaded in parallel by download_all
+    pub concurrency: usize,
+    // Digests all downloaded files must have, whatever the other sources say
+    pub expected: Vec<(HashAlgorithm, String)>,
+    // When set, checksums files of releases are only used if signed by one of these keys,
+    // and assets having a signature must be signed by one of them.
+    pub allowed_signers: Option<AllowedSigners>,
+    // Namespace the signatures must have been made for, see ssh-keygen -Y sign -n
+    pub signature_namespace: String,
+    // When set, assets must have a minisign signature (<asset>.minisig) made with this key,
+    // unless the download request gives another key
+    pub minisign_key: Option<MinisignKey>,
+    // When set, checksums files of releases are only used if they have an OpenPGP signature
+    // by one of these keys, and assets must be covered by such a signature, either their
+    // own (eg <asset>.asc) or the one of the checksums file used.
+    pub keyring: Option<Keyring>,
+    // When set, assets must have a Sigstore bundle (eg <asset>.sigstore.json) whose
+    // certificate was issued by one of these CAs and whose signature was logged in one of
+    // these transparency logs. With an attestation policy, bundles are only required if a
+    // certificate identity is given.
+    pub sigstore_trust_root: Option<SigstoreTrustRoot>,
+    // Identity the certificates of Sigstore bundles must have been issued to, unless the
+    // download request gives another one
+    pub sigstore_identity: Option<SigstoreIdentity>,
+    // When set, assets must have a GitHub artifact attestation satisfying this policy,
+    // verified against the Sigstore trust root
+    pub attestation_policy: Option<AttestationPolicy>,
 }
 
 impl Default for Downloader {
@@ -34,6 +101,22 @@ impl Downloader {
         Self {
             client: GitHubClient::new(),
             progress_init: Box::new(progress_init),
+            resume: true,
+            overwrite: false,
+            mirror: None,
+            policy: VerificationPolicy::default(),
+            allow_weak_algorithms: false,
+            algorithms: vec![],
+            extractor: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            expected: vec![],
+            allowed_signers: None,
+            signature_namespace: DEFAULT_SIGNATURE_NAMESPACE.to_string(),
+            minisign_key: None,
+            keyring: None,
+            sigstore_trust_root: None,
+            sigstore_identity: None,
+            attestation_policy: None,
         }
     }
 
@@ -49,110 +132,1407 @@ impl Downloader {
             ..self
         }
     }
+
+    pub fn with_resume(self, resume: bool) -> Self {
+        Self { resume, ..self }
+    }
+
+    pub fn with_overwrite(self, overwrite: bool) -> Self {
+        Self { overwrite, ..self }
+    }
+
+    pub fn with_mirror(self, mirror: ChecksumsMirror) -> Self {
+        Self {
+            mirror: Some(mirror),
+            ..self
+        }
+    }
+
+    pub fn with_policy(self, policy: VerificationPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn with_weak_algorithms(self, allow_weak_algorithms: bool) -> Self {
+        Self {
+            allow_weak_algorithms,
+            ..self
+        }
+    }
+
+    pub fn with_algorithms(self, algorithms: Vec<HashAlgorithm>) -> Self {
+        Self { algorithms, ..self }
+    }
+
+    pub fn with_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    // Digests are parsed with Hasher::parse_digest, eg from sha256:<hex>
+    pub fn with_expected(self, expected: Vec<(HashAlgorithm, String)>) -> Self {
+        Self { expected, ..self }
+    }
+
+    pub fn with_allowed_signers(self, allowed_signers: AllowedSigners) -> Self {
+        Self {
+            allowed_signers: Some(allowed_signers),
+            ..self
+        }
+    }
+
+    pub fn with_signature_namespace(self, signature_namespace: String) -> Self {
+        Self {
+            signature_namespace,
+            ..self
+        }
+    }
+
+    pub fn with_minisign_key(self, minisign_key: MinisignKey) -> Self {
+        Self {
+            minisign_key: Some(minisign_key),
+            ..self
+        }
+    }
+
+    pub fn with_keyring(self, keyring: Keyring) -> Self {
+        Self {
+            keyring: Some(keyring),
+            ..self
+        }
+    }
+
+    pub fn with_sigstore_trust_root(self, sigstore_trust_root: SigstoreTrustRoot) -> Self {
+        Self {
+            sigstore_trust_root: Some(sigstore_trust_root),
+            ..self
+        }
+    }
+
+    pub fn with_sigstore_identity(self, sigstore_identity: SigstoreIdentity) -> Self {
+        Self {
+            sigstore_identity: Some(sigstore_identity),
+            ..self
+        }
+    }
+
+    pub fn with_attestation_policy(self, attestation_policy: AttestationPolicy) -> Self {
+        Self {
+            attestation_policy: Some(attestation_policy),
+            ..self
+        }
+    }
+
+    // Extraction only applies to files downloaded to disk, not to files streamed to a writer
+    pub fn with_extractor(self, extractor: Extractor) -> Self {
+        Self {
+            extractor: Some(extractor),
+            ..self
+        }
+    }
+
     pub async fn download_and_verify(
         &self,
         url: url::Url,
         output_path: Option<&Path>,
         quiet: bool,
     ) -> Result<DownloadResult> {
-        // Parse GitHub URL
-        let (owner, repo, tag, filename) =
-            GitHubClient::parse_github_url(url.to_string().as_str())?;
+        let asset_ref = GitHubClient::parse_release_url(url.as_str())?;
+        self.download_asset(&asset_ref, output_path, quiet).await
+    }
 
-        // Get release information
-        let release = self.client.get_release(&owner, &repo, &tag).await?;
+    // Downloads the asset to output_path, or to a file named like the asset in the current
+    // directory, once its hash has been verified.
+    pub async fn download_asset(
+        &self,
+        asset_ref: &AssetRef,
+        output_path: Option<&Path>,
+        quiet: bool,
+    ) -> Result<DownloadResult> {
+        let request = DownloadRequest {
+            output: output_path.map(Path::to_path_buf),
+            ..DownloadRequest::new(asset_ref.clone())
+        };
+        self.download(&request, quiet).await
+    }
 
-        // Find the asset
-        let asset = release
-            .assets
-            .into_iter()
-            .find(|a| a.name == filename)
-            .ok_or_else(|| Error::AssetNotFound(filename.clone()))?;
+    // Downloads the file of the request, which is also verified against the digests it pins
+    pub async fn download(&self, request: &DownloadRequest, quiet: bool) -> Result<DownloadResult> {
+        self.download_with(
+            request,
+            Progress::from_quiet(quiet),
+            &ReleaseCache::default(),
+        )
+        .await
+    }
 
-        // Parse the digest
-        let (algorithm, expected_hash) = Hasher::parse_digest(&asset.digest)?;
+    async fn download_with(
+        &self,
+        request: &DownloadRequest,
+        progress: Progress<'_>,
+        releases: &ReleaseCache,
+    ) -> Result<DownloadResult> {
+        let resolved = self.resolve_asset(request, releases).await?;
+        self.download_resolved(request.output.as_deref(), resolved, progress)
+            .await
+    }
 
-        // Download the file
-        let download_path = output_path.unwrap_or_else(|| Path::new(&filename));
-        let actual_hash = self
+    async fn download_resolved(
+        &self,
+        output_path: Option<&Path>,
+        mut resolved: ResolvedAsset,
+        progress: Progress<'_>,
+    ) -> Result<DownloadResult> {
+        let algorithms = self.hash_algorithms(&resolved);
+        let asset = resolved.asset.clone();
+
+        // Download the file to a temporary file next to the destination, which is only
+        // moved in place once its hash has been verified.
+        let download_path = output_path.unwrap_or_else(|| Path::new(&asset.name));
+        self.check_overwrite(download_path)?;
+        let part_path = Self::part_path(download_path);
+        let digests = match self
             .download_file(
                 &asset.browser_download_url,
-                download_path,
-                &algorithm,
-                quiet,
+                &part_path,
+                &algorithms,
+                progress,
             )
-            .await?;
+            .await
+        {
+            Ok(digests) => digests,
+            Err(e) => {
+                // Keep interrupted transfers around so they can be resumed
+                if !(self.resume && matches!(e, Error::RequestError(_))) {
+                    Self::remove_part_file(&part_path);
+                }
+                return Err(e);
+            }
+        };
 
-        // Verify hash
-        if expected_hash != actual_hash {
-            return Err(Error::HashVerificationFailed {
-                expected: expected_hash,
-                actual: actual_hash,
-            });
+        // Verify hash and signatures
+        let verified = async {
+            self.verify(&mut resolved, &digests)?;
+            self.verify_content(&mut resolved, &part_path).await?;
+            self.verify_attestation(&mut resolved, &digests).await
+        }
+        .await;
+        if let Err(e) = verified {
+            Self::remove_part_file(&part_path);
+            return Err(e);
         }
 
+        let (part, path, overwrite) = (
+            part_path.clone(),
+            download_path.to_path_buf(),
+            self.overwrite,
+        );
+        let size = match run_blocking(move || Self::persist(&part, &path, overwrite)).await {
+            Ok(size) => size,
+            Err(e) => {
+                Self::remove_part_file(&part_path);
+                return Err(e);
+            }
+        };
+
+        // Only extract verified files
+        let extracted = match self.extractor.clone() {
+            Some(extractor) => {
+                let (path, name, overwrite) = (
+                    download_path.to_path_buf(),
+                    asset.name.clone(),
+                    self.overwrite,
+                );
+                run_blocking(move || extractor.extract(&path, &name, overwrite)).await?
+            }
+            None => vec![],
+        };
+
+        let (algorithm, hash) = digests[0].clone();
         Ok(DownloadResult {
             path: download_path.to_path_buf(),
-            size: std::fs::metadata(download_path)?.len(),
+            size,
+            tag: resolved.tag,
+            asset: asset.name,
+            url: asset.browser_download_url,
+            algorithm,
+            hash,
+            digests,
+            sources: resolved.sources,
+            signatures: resolved.signatures,
+            extracted,
+        })
+    }
+
+    // Downloads the files, up to self.concurrency of them in parallel, carrying on when one
+    // fails. Each release is only fetched once. Unless quiet, a progress bar is displayed
+    // for each file. Results are in the order of the requests.
+    // All the assets are resolved before downloading any of them, so that files which would
+    // be downloaded to the same path fail with Error::DuplicateOutput.
+    pub async fn download_all(
+        &self,
+        requests: &[DownloadRequest],
+        quiet: bool,
+    ) -> Vec<Result<DownloadResult>> {
+        let releases = ReleaseCache::default();
+        let concurrency = self.concurrency.max(1);
+        let resolved: Vec<Result<ResolvedAsset>> = futures::stream::iter(requests)
+            .map(|request| self.resolve_asset(request, &releases))
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        // Paths are made absolute, so that eg ./file and file are the same
+        let outputs: Vec<Option<PathBuf>> = requests
+            .iter()
+            .zip(&resolved)
+            .map(|(request, resolved)| {
+                let asset = &resolved.as_ref().ok()?.asset;
+                let path = request
+                    .output
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(&asset.name));
+                Some(std::path::absolute(&path).unwrap_or(path))
+            })
+            .collect();
+        let mut counts: HashMap<&PathBuf, usize> = HashMap::new();
+        for output in outputs.iter().flatten() {
+            *counts.entry(output).or_default() += 1;
+        }
+
+        let bars = MultiProgress::new();
+        let progress = if quiet {
+            Progress::Hidden
+        } else {
+            Progress::Multi(&bars)
+        };
+        // The downloads run concurrently in the current task, as the progress bar
+        // initialisation closure cannot be shared between threads. Their blocking file
+        // operations are run on the blocking threads of the runtime.
+        let counts = &counts;
+        futures::stream::iter(requests.iter().zip(resolved).zip(&outputs))
+            .map(|((request, resolved), output)| async move {
+                let resolved = resolved?;
+                if let Some(output) = output.as_ref().filter(|o| counts[o] > 1) {
+                    return Err(Error::DuplicateOutput(output.clone()));
+                }
+                self.download_resolved(request.output.as_deref(), resolved, progress)
+                    .await
+            })
+            .buffered(concurrency)
+            .collect()
+            .await
+    }
+
+    // Streams the file to the writer, e.g. stdout. As data is written as it is received,
+    // the hash can only be verified at the end: the caller must not trust what was written
+    // if an error is returned.
+    pub async fn download_and_verify_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        url: url::Url,
+        writer: &mut W,
+        quiet: bool,
+    ) -> Result<StreamResult> {
+        let asset_ref = GitHubClient::parse_release_url(url.as_str())?;
+        self.download_asset_to_writer(&asset_ref, writer, quiet)
+            .await
+    }
+
+    // Streams the asset to the writer, with the same caveat as download_and_verify_to_writer
+    pub async fn download_asset_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        asset_ref: &AssetRef,
+        writer: &mut W,
+        quiet: bool,
+    ) -> Result<StreamResult> {
+        let mut resolved = self
+            .resolve_asset(
+                &DownloadRequest::new(asset_ref.clone()),
+                &ReleaseCache::default(),
+            )
+            .await?;
+        let algorithms = self.hash_algorithms(&resolved);
+
+        // The file cannot be read again, so its minisign and OpenPGP signatures are
+        // verified as it is streamed
+        let content = std::mem::take(&mut resolved.content);
+        let mut verifications = content.verifications(self.keyring.as_ref())?;
+        let mut writer = TeeWriter {
+            writer,
+            verifications: &mut verifications,
+        };
+
+        let response = self
+            .request(&resolved.asset.browser_download_url, 0)
+            .await?;
+        let mut hasher = MultiHasher::new(&algorithms);
+        let size = self
+            .stream_response(
+                response,
+                &mut writer,
+                &mut hasher,
+                0,
+                Progress::from_quiet(quiet),
+            )
+            .await?;
+        writer.flush().await?;
+
+        // Verify hash and signatures
+        let digests = hasher.finalize();
+        self.verify(&mut resolved, &digests)?;
+        let verified = verifications.finish(&resolved.asset.name)?;
+        resolved.signatures.extend(verified);
+        self.verify_attestation(&mut resolved, &digests).await?;
+
+        let (algorithm, hash) = digests[0].clone();
+        Ok(StreamResult {
+            size,
+            tag: resolved.tag,
+            asset: resolved.asset.name,
+            url: resolved.asset.browser_download_url,
             algorithm,
-            hash: actual_hash,
+            hash,
+            digests,
+            sources: resolved.sources,
+            signatures: resolved.signatures,
         })
     }
 
+    // Verifies the checksum of a file already downloaded from url, using the same sources
+    // and policy as downloads.
+    pub async fn verify_file(&self, url: url::Url, path: &Path) -> Result<DownloadResult> {
+        let asset_ref = GitHubClient::parse_release_url(url.as_str())?;
+        let mut resolved = self
+            .resolve_asset(&DownloadRequest::new(asset_ref), &ReleaseCache::default())
+            .await?;
+        let algorithms = self.hash_algorithms(&resolved);
+
+        let mut hasher = MultiHasher::new(&algorithms);
+        let file = File::open(path)?;
+        let (size, digests) = run_blocking(move || {
+            let size = hasher.update_reader(file)?;
+            Ok((size, hasher.finalize()))
+        })
+        .await?;
+        self.verify(&mut resolved, &digests)?;
+        self.verify_content(&mut resolved, path).await?;
+        self.verify_attestation(&mut resolved, &digests).await?;
+
+        let (algorithm, hash) = digests[0].clone();
+        Ok(DownloadResult {
+            path: path.to_path_buf(),
+            size,
+            tag: resolved.tag,
+            asset: resolved.asset.name,
+            url: resolved.asset.browser_download_url,
+            algorithm,
+            hash,
+            digests,
+            sources: resolved.sources,
+            signatures: resolved.signatures,
+            extracted: vec![],
+        })
+    }
+
+    // Finds the release asset and collects its expected checksum from each source, checking
+    // they satisfy the verification policy.
+    // The digests pinned by the request and by self.expected are always checked, in addition
+    // to the other sources.
+    async fn resolve_asset(
+        &self,
+        request: &DownloadRequest,
+        releases: &ReleaseCache,
+    ) -> Result<ResolvedAsset> {
+        let asset_ref = &request.asset;
+        let pinned = &request.expected;
+        let AssetRef { owner, repo, .. } = asset_ref;
+        if self.attestation_policy.is_some() && self.sigstore_trust_root.is_none() {
+            return Err(Error::InvalidPolicy(
+                "a Sigstore trust root is needed to verify attestations".to_string(),
+            ));
+        }
+        let (release, tag, asset) = self.find_asset(asset_ref, releases).await?;
+        let filename = &asset.name;
+
+        let pinned: Vec<(HashAlgorithm, String)> =
+            self.expected.iter().chain(pinned).cloned().collect();
+        // Pinned digests are never ignored, so they must be usable
+        if let Some((algorithm, _)) = pinned
+            .iter()
+            .find(|(algorithm, _)| algorithm.is_weak() && !self.allow_weak_algorithms)
+        {
+            return Err(Error::UnsupportedAlgorithm(format!(
+                "{} is a weak algorithm, which is not allowed",
+                algorithm
+            )));
+        }
+        // With digests pinned, the API digest is always consulted too, so that a release
+        // asset replaced since its digest was pinned is reported
+        let has_pinned = !pinned.is_empty();
+        let pinned_sources = pinned
+            .into_iter()
+            .map(|digest| (ChecksumSource::Pinned, Some(digest)));
+        let other_sources = [
+            ChecksumSource::AssetDigest,
+            ChecksumSource::ReleaseChecksums,
+            ChecksumSource::Mirror,
+        ]
+        .map(|source| (source, None));
+
+        let mut sources = vec![];
+        let mut signatures = vec![];
+        for (source, pinned) in pinned_sources.chain(other_sources) {
+            if source == ChecksumSource::Mirror && self.mirror.is_none() {
+                continue;
+            }
+            // Signed checksums files are always consulted too when signers are given, so that
+            // their signature is checked, and may cover the asset for the keyring. The mirror
+            // is consulted whatever the policy, as it was explicitly requested.
+            let signers_given = self.allowed_signers.is_some() || self.keyring.is_some();
+            let always_consulted =
+                matches!(source, ChecksumSource::Pinned | ChecksumSource::Mirror)
+                    || (source == ChecksumSource::AssetDigest && has_pinned)
+                    || (source == ChecksumSource::ReleaseChecksums && signers_given);
+            if !always_consulted
+                && self.policy.stops_at_first_available()
+                && sources.iter().any(SourceReport::is_available)
+            {
+                sources.push(SourceReport {
+                    source,
+                    expected: None,
+                    status: SourceStatus::NotConsulted,
+                });
+                continue;
+            }
+
+            let expected = match source {
+                ChecksumSource::Pinned => {
+                    pinned.ok_or_else(|| Error::ChecksumNotFound("no digest pinned".to_string()))
+                }
+                ChecksumSource::AssetDigest => match asset.digest.as_deref() {
+                    Some(digest) if !digest.is_empty() => Hasher::parse_digest(digest),
+                    _ => Err(Error::ChecksumNotFound("no digest published".to_string())),
+                },
+                ChecksumSource::ReleaseChecksums => self
+                    .release_checksum(&release, filename)
+                    .await
+                    .map(|(expected, verified)| {
+                        signatures.extend(verified);
+                        expected
+                    }),
+                ChecksumSource::Mirror => {
+                    self.mirror_checksum(owner, repo, &tag, &release, filename)
+                        .await
+                }
+            };
+            // A checksums file with an invalid signature may have been tampered with
+            let expected = match expected {
+                Err(
+                    e @ (Error::SignatureVerificationFailed(_)
+                    | Error::InvalidSignature(_)
+                    | Error::UnknownSigner(_)
+                    | Error::KeyExpired(_)),
+                ) => return Err(e),
+                expected => expected,
+            };
+            let expected = expected.and_then(|(algorithm, hash)| {
+                if algorithm.is_weak() && !self.allow_weak_algorithms {
+                    Err(Error::ChecksumNotFound(format!(
+                        "only a checksum using the weak algorithm {} is available",
+                        algorithm
+                    )))
+                } else {
+                    Ok((algorithm, hash))
+                }
+            });
+            sources.push(match expected {
+                Ok(expected) => SourceReport {
+                    source,
+                    expected: Some(expected),
+                    status: SourceStatus::Unverified,
+                },
+                Err(e) => SourceReport {
+                    source,
+                    expected: None,
+                    status: SourceStatus::Unavailable(match e {
+                        Error::ChecksumNotFound(reason) => reason,
+                        e => e.to_string(),
+                    }),
+                },
+            });
+        }
+
+        self.policy.check_sources(filename, &sources)?;
+        let signature = match self.allowed_signers {
+            Some(_) => self.asset_signature(&release, filename).await?,
+            None => None,
+        };
+        let minisign = match request.minisign_key.as_ref().or(self.minisign_key.as_ref()) {
+            Some(key) => Some(self.minisign_signature(&release, filename, key).await?),
+            None => None,
+        };
+        let pgp = match self.keyring {
+            Some(_) => self.pgp_signature(&release, filename).await?,
+            None => None,
+        };
+        // Checksums files are only used if signed, so the asset is covered by a signature
+        // once the checksum is verified
+        let signed_checksum = sources
+            .iter()
+            .any(|r| r.source == ChecksumSource::ReleaseChecksums && r.expected.is_some());
+        if self.keyring.is_some() && pgp.is_none() && !signed_checksum {
+            return Err(Error::SignatureNotFound(format!(
+                "neither {} nor a checksums file used for it has an OpenPGP signature",
+                filename
+            )));
+        }
+        let identity = request
+            .sigstore_identity
+            .as_ref()
+            .or(self.sigstore_identity.as_ref());
+        let sigstore = match (&self.sigstore_trust_root, identity) {
+            (Some(_), Some(identity)) => {
+                let (name, bundle) = self.sigstore_bundle(&release, filename).await?;
+                Some((name, bundle, identity.clone()))
+            }
+            // The trust root may only be given to verify attestations
+            (Some(_), None) if self.attestation_policy.is_none() => {
+                return Err(Error::InvalidPolicy(
+                    "a certificate identity and issuer are needed to verify Sigstore bundles"
+                        .to_string(),
+                ))
+            }
+            _ => None,
+        };
+        Ok(ResolvedAsset {
+            owner: owner.clone(),
+            repo: repo.clone(),
+            asset,
+            tag,
+            sources,
+            signature,
+            sigstore,
+            content: ContentSignatures { minisign, pgp },
+            signatures,
+        })
+    }
+
+    // Finds the release and the asset designated by asset_ref, returning the release tag
+    // and the asset. Checksums are not looked up.
+    pub async fn resolve(&self, asset_ref: &AssetRef) -> Result<(String, GitHubAsset)> {
+        let (_, tag, asset) = self.find_asset(asset_ref, &ReleaseCache::default()).await?;
+        Ok((tag, asset))
+    }
+
+    // Resolves the assets, up to self.concurrency of them in parallel, each release being
+    // only fetched once. Results are in the order of asset_refs.
+    pub async fn resolve_all(&self, asset_refs: &[AssetRef]) -> Vec<Result<(String, GitHubAsset)>> {
+        let releases = ReleaseCache::default();
+        futures::stream::iter(asset_refs)
+            .map(|asset_ref| async {
+                let (_, tag, asset) = self.find_asset(asset_ref, &releases).await?;
+                Ok((tag, asset))
+            })
+            .buffered(self.concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn find_asset(
+        &self,
+        asset_ref: &AssetRef,
+        releases: &ReleaseCache,
+    ) -> Result<(GitHubRelease, String, GitHubAsset)> {
+        let AssetRef {
+            owner,
+            repo,
+            release: release_spec,
+            asset: selector,
+        } = asset_ref;
+
+        // Get release information
+        let release = releases
+            .get(&self.client, owner, repo, release_spec)
+            .await?;
+        let tag = match release_spec {
+            ReleaseSpec::Tag(tag) => tag.clone(),
+            _ => release.tag_name.clone(),
+        };
+
+        // Find the asset
+        let asset = selector.select(&release.assets)?.clone();
+        Ok((release, tag, asset))
+    }
+
+    // The algorithms to compute digests for: those of the available sources, the first one
+    // being reported as the download's hash, followed by the one of the asset's signature
+    // and those requested.
+    fn hash_algorithms(&self, resolved: &ResolvedAsset) -> Vec<HashAlgorithm> {
+        let mut algorithms: Vec<HashAlgorithm> = resolved
+            .sources
+            .iter()
+            .filter_map(|r| r.expected.as_ref().map(|(algorithm, _)| algorithm.clone()))
+            .chain(
+                resolved
+                    .signature
+                    .iter()
+                    .map(|(_, signature)| signature.hash_algorithm()),
+            )
+            .chain(
+                resolved
+                    .sigstore
+                    .iter()
+                    .map(|(_, bundle, _)| bundle.hash_algorithm()),
+            )
+            .chain(self.algorithms.iter().cloned())
+            // Attestations are looked up by sha256 digest
+            .chain(
+                self.attestation_policy
+                    .iter()
+                    .map(|_| HashAlgorithm::Sha256),
+            )
+            .collect();
+        if algorithms.is_empty() {
+            algorithms.push(HashAlgorithm::Sha256);
+        }
+        algorithms
+    }
+
+    async fn mirror_checksum(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        release: &GitHubRelease,
+        filename: &str,
+    ) -> Result<(HashAlgorithm, String)> {
+        let Some(mirror) = &self.mirror else {
+            return Err(Error::MirrorError("no mirror configured".to_string()));
+        };
+        let checksums_files: Vec<&str> = release
+            .assets
+            .iter()
+            .map(|a| a.name.as_str())
+            .filter(|n| {
+                checksums::is_asset_checksum_file(n, filename) || checksums::is_checksums_file(n)
+            })
+            .collect();
+        let entry = mirror
+            .get_checksum(owner, repo, tag, &checksums_files, filename)
+            .await?;
+        Ok((entry.algorithm, entry.hash))
+    }
+
+    // Checks the digests of the downloaded file against the checksums found, and its
+    // signature if it has one.
+    fn verify(
+        &self,
+        resolved: &mut ResolvedAsset,
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<()> {
+        self.policy.verify(&mut resolved.sources, digests)?;
+        if let (Some(signers), Some((name, signature))) =
+            (&self.allowed_signers, &resolved.signature)
+        {
+            let signer = signers.verify_digest(&self.signature_namespace, signature, digests)?;
+            resolved.signatures.push(VerifiedSignature {
+                file: resolved.asset.name.clone(),
+                signature: name.clone(),
+                signer,
+                comment: None,
+            });
+        }
+        if let (Some(trust_root), Some((name, bundle, identity))) =
+            (&self.sigstore_trust_root, &resolved.sigstore)
+        {
+            let signer = bundle.verify_digest(trust_root, identity, digests)?;
+            resolved.signatures.push(VerifiedSignature {
+                file: resolved.asset.name.clone(),
+                signature: name.clone(),
+                signer: signer.identity,
+                comment: Some(format!(
+                    "issued by {}, Rekor log index {}",
+                    signer.issuer, signer.log_index
+                )),
+            });
+        }
+        Ok(())
+    }
+
+    // Checks that the asset has an attestation satisfying the policy, if one is required
+    async fn verify_attestation(
+        &self,
+        resolved: &mut ResolvedAsset,
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<()> {
+        let (Some(policy), Some(trust_root)) =
+            (&self.attestation_policy, &self.sigstore_trust_root)
+        else {
+            return Ok(());
+        };
+        let Some((_, sha256)) = digests.iter().find(|(a, _)| *a == HashAlgorithm::Sha256) else {
+            return Err(Error::HashError("no sha256 digest computed".to_string()));
+        };
+        let attestation = self
+            .client
+            .verify_attestations(&resolved.owner, &resolved.repo, sha256, trust_root, policy)
+            .await?;
+        let build = &attestation.build;
+        resolved.signatures.push(VerifiedSignature {
+            file: resolved.asset.name.clone(),
+            signature: attestation
+                .statement
+                .map(|statement| statement.predicate_type)
+                .unwrap_or_default(),
+            signer: build
+                .build_signer_uri
+                .clone()
+                .unwrap_or(attestation.signer.identity),
+            comment: Some(format!(
+                "attestation of {} at {}, Rekor log index {}",
+                build.source_repository_uri.as_deref().unwrap_or_default(),
+                build.source_repository_ref.as_deref().unwrap_or_default(),
+                attestation.signer.log_index
+            )),
+        });
+        Ok(())
+    }
+
+    // The minisign signature of the asset, which must have been published, eg as
+    // file.tar.gz.minisig
+    async fn minisign_signature(
+        &self,
+        release: &GitHubRelease,
+        filename: &str,
+        key: &MinisignKey,
+    ) -> Result<MinisignCheck> {
+        let name = format!("{}{}", filename, MINISIGN_SIGNATURE_EXTENSION);
+        let asset = release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| Error::SignatureNotFound(format!("{} is not in the release", name)))?;
+        let signature = self.client.get_asset_content(asset).await?.parse()?;
+        Ok(MinisignCheck {
+            name,
+            signature,
+            key: key.clone(),
+        })
+    }
+
+    // The Sigstore bundle of the asset, which must have been published, eg as
+    // file.tar.gz.sigstore.json
+    async fn sigstore_bundle(
+        &self,
+        release: &GitHubRelease,
+        filename: &str,
+    ) -> Result<(String, SigstoreBundle)> {
+        for extension in SIGSTORE_BUNDLE_EXTENSIONS {
+            let name = format!("{}{}", filename, extension);
+            if let Some(asset) = release.assets.iter().find(|a| a.name == name) {
+                let bundle = self.client.get_asset_content(asset).await?.parse()?;
+                return Ok((name, bundle));
+            }
+        }
+        Err(Error::SignatureNotFound(format!(
+            "no Sigstore bundle of {} is in the release",
+            filename
+        )))
+    }
+
+    // Checks the minisign and OpenPGP signatures of the asset, if it must have them,
+    // against its content in the file at path
+    async fn verify_content(&self, resolved: &mut ResolvedAsset, path: &Path) -> Result<()> {
+        let signatures = std::mem::take(&mut resolved.content);
+        if signatures.minisign.is_none() && signatures.pgp.is_none() {
+            return Ok(());
+        }
+        let keyring = self.keyring.clone();
+        let (path, name) = (path.to_path_buf(), resolved.asset.name.clone());
+        let verified = run_blocking(move || {
+            let mut verifications = signatures.verifications(keyring.as_ref())?;
+            std::io::copy(&mut File::open(path)?, &mut verifications)?;
+            verifications.finish(&name)
+        })
+        .await?;
+        resolved.signatures.extend(verified);
+        Ok(())
+    }
+
+    // The SSH signature published for the asset, eg file.tar.gz.sig, if there is one
+    async fn asset_signature(
+        &self,
+        release: &GitHubRelease,
+        filename: &str,
+    ) -> Result<Option<(String, SshSignature)>> {
+        let name = format!("{}{}", filename, SSH_SIGNATURE_EXTENSION);
+        match release.assets.iter().find(|a| a.name == name) {
+            Some(asset) => {
+                let content = self.client.get_asset_bytes(asset).await?;
+                // The extension is also used for OpenPGP signatures
+                if PgpSignature::is_openpgp(&content) {
+                    return Ok(None);
+                }
+                let signature = String::from_utf8_lossy(&content).parse()?;
+                Ok(Some((name, signature)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // The OpenPGP signature published for the asset, eg file.tar.gz.asc, if there is one
+    async fn pgp_signature(
+        &self,
+        release: &GitHubRelease,
+        filename: &str,
+    ) -> Result<Option<(String, PgpSignature)>> {
+        for extension in OPENPGP_SIGNATURE_EXTENSIONS {
+            let name = format!("{}{}", filename, extension);
+            let Some(asset) = release.assets.iter().find(|a| a.name == name) else {
+                continue;
+            };
+            let content = self.client.get_asset_bytes(asset).await?;
+            // .sig files may be SSH signatures
+            if extension == SSH_SIGNATURE_EXTENSION && !PgpSignature::is_openpgp(&content) {
+                continue;
+            }
+            return Ok(Some((name, PgpSignature::from_bytes(&content)?)));
+        }
+        Ok(None)
+    }
+
+    // Looks for the checksum of filename in the checksums files of the release, starting
+    // with the file dedicated to that asset (eg file.tar.gz.sha256) if there is one.
+    // With allowed signers or a keyring, only checksums files signed accordingly are used,
+    // and the verified signatures are returned with the checksum.
+    async fn release_checksum(
+        &self,
+        release: &GitHubRelease,
+        filename: &str,
+    ) -> Result<((HashAlgorithm, String), Vec<VerifiedSignature>)> {
+        let dedicated = release
+            .assets
+            .iter()
+            .filter(|a| checksums::is_asset_checksum_file(&a.name, filename));
+        let shared = release
+            .assets
+            .iter()
+            .filter(|a| checksums::is_checksums_file(&a.name));
+
+        let mut unsigned = false;
+        // Checksums files which could not be downloaded, the others may still have an entry
+        let mut failed = vec![];
+        for checksums_asset in dedicated.chain(shared) {
+            let content = match self.client.get_asset_content(checksums_asset).await {
+                Ok(content) => content,
+                Err(e) => {
+                    failed.push(format!("{}: {}", checksums_asset.name, e));
+                    continue;
+                }
+            };
+            let Some(entry) = checksums::lookup(&checksums_asset.name, &content, filename) else {
+                continue;
+            };
+            let mut signatures = vec![];
+            if let Some(signers) = &self.allowed_signers {
+                let Some((name, signature)) =
+                    self.asset_signature(release, &checksums_asset.name).await?
+                else {
+                    unsigned = true;
+                    continue;
+                };
+                let signer =
+                    signers.verify(&self.signature_namespace, &signature, content.as_bytes())?;
+                signatures.push(VerifiedSignature {
+                    file: checksums_asset.name.clone(),
+                    signature: name,
+                    signer,
+                    comment: None,
+                });
+            }
+            if let Some(keyring) = &self.keyring {
+                let Some((name, signature)) =
+                    self.pgp_signature(release, &checksums_asset.name).await?
+                else {
+                    unsigned = true;
+                    continue;
+                };
+                let signer = keyring.verify(&signature, content.as_bytes())?;
+                signatures.push(VerifiedSignature {
+                    file: checksums_asset.name.clone(),
+                    signature: name,
+                    signer,
+                    comment: None,
+                });
+            }
+            return Ok(((entry.algorithm, entry.hash), signatures));
+        }
+
+        let mut reason = if unsigned {
+            "no signed checksums file in the release has an entry for it".to_string()
+        } else {
+            "no checksums file in the release has an entry for it".to_string()
+        };
+        if !failed.is_empty() {
+            reason = format!("{} (failed to download {})", reason, failed.join(", "));
+        }
+        Err(Error::ChecksumNotFound(reason))
+    }
+
+    fn check_overwrite(&self, path: &Path) -> Result<()> {
+        if !self.overwrite && path.exists() {
+            return Err(Error::FileExists(path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    // Atomically moves the verified temporary file to its destination and returns its size.
+    // Without overwrite, the file is hard linked instead of renamed, as linking fails if the
+    // destination was created since check_overwrite.
+    fn persist(part_path: &Path, path: &Path, overwrite: bool) -> Result<u64> {
+        let size = std::fs::metadata(part_path)?.len();
+        if overwrite {
+            std::fs::rename(part_path, path)?;
+        } else {
+            std::fs::hard_link(part_path, path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => Error::FileExists(path.to_path_buf()),
+                _ => e.into(),
+            })?;
+            std::fs::remove_file(part_path)?;
+        }
+        Ok(size)
+    }
+
+    fn remove_part_file(part_path: &Path) {
+        // Errors are ignored as we are already handling a failure
+        let _ = std::fs::remove_file(part_path);
+    }
+
+    // Downloads the file to part_path, which is flushed to disk before returning the digests.
+    // If part_path is already present and resuming is enabled, its content is hashed and
+    // only the remaining bytes are requested.
     async fn download_file(
         &self,
         url: &str,
-        path: &Path,
-        algorithm: &HashAlgorithm,
-        quiet: bool,
-    ) -> Result<String> {
-        let response = reqwest::get(url).await?;
+        part_path: &Path,
+        algorithms: &[HashAlgorithm],
+        progress: Progress<'_>,
+    ) -> Result<Vec<(HashAlgorithm, String)>> {
+        let mut hasher = MultiHasher::new(algorithms);
+
+        let mut already_downloaded = 0;
+        if self.resume {
+            let path = part_path.to_path_buf();
+            (hasher, already_downloaded) = run_blocking(move || {
+                let size = Self::hash_partial_download(&path, &mut hasher)?;
+                Ok((hasher, size))
+            })
+            .await?;
+        }
+
+        let mut response = self.request(url, already_downloaded).await?;
+
+        // Servers ignoring the Range header send the full file, in which case we restart.
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT
+            && Self::content_range_start(&response) == Some(already_downloaded);
+        if !resumed && already_downloaded > 0 {
+            // The server could not satisfy the range (e.g. the file changed), or sent
+            // another range than the one requested
+            if response.status() != StatusCode::OK {
+                response = self.request(url, 0).await?;
+            }
+            already_downloaded = 0;
+            hasher = MultiHasher::new(algorithms);
+        }
+
+        let mut file = if already_downloaded > 0 {
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(part_path)
+                .await?
+        } else {
+            tokio::fs::File::create(part_path).await?
+        };
+
+        self.stream_response(
+            response,
+            &mut file,
+            &mut hasher,
+            already_downloaded,
+            progress,
+        )
+        .await?;
+        file.flush().await?;
+        file.sync_all().await?;
+
+        Ok(hasher.finalize())
+    }
 
+    // Sends a GET request with the GitHub client, asking for the content starting at offset
+    // if it is not 0. A 416 response to a range request is returned as is so the caller can
+    // restart.
+    async fn request(&self, url: &str, offset: u64) -> Result<reqwest::Response> {
+        let mut request = self.client.file_request(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await?;
+
+        if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(response);
+        }
         if !response.status().is_success() {
             return Err(Error::RequestError(
                 response.error_for_status().unwrap_err(),
             ));
         }
+        Ok(response)
+    }
+
+    // The offset of the content of a partial response, from its Content-Range header, eg
+    // bytes 5-11/12
+    fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+        let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+        let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+        start.trim().parse().ok()
+    }
 
+    // Writes the response body to the writer and feeds it to the hasher, reporting progress
+    // as requested. already_downloaded is the size of the content received previously.
+    // Returns the total size of the file.
+    async fn stream_response<W: AsyncWrite + Unpin>(
+        &self,
+        response: reqwest::Response,
+        writer: &mut W,
+        hasher: &mut MultiHasher,
+        already_downloaded: u64,
+        progress: Progress<'_>,
+    ) -> Result<u64> {
         let total_size = response
             .content_length()
-            .ok_or_else(|| Error::from(std::io::Error::other("Missing content length header")))?;
+            .ok_or_else(|| Error::from(std::io::Error::other("Missing content length header")))?
+            + already_downloaded;
 
-        let pb = if !quiet {
-            let pb = (self.progress_init)(total_size);
-            Some(pb)
-        } else {
-            None
+        let pb = match progress {
+            Progress::Hidden => None,
+            Progress::Bar => Some((self.progress_init)(total_size)),
+            Progress::Multi(bars) => Some(bars.add((self.progress_init)(total_size))),
         };
+        if let Some(pbv) = &pb {
+            pbv.set_position(already_downloaded);
+        }
 
-        let mut file = File::create(path)?;
         let mut stream = response.bytes_stream();
 
-        let mut hasher = match algorithm {
-            HashAlgorithm::Sha256 => Sha256::new(),
-        };
-
         while let Some(chunk) = stream.try_next().await? {
             if let Some(pbv) = &pb {
                 pbv.inc(chunk.len() as u64);
             }
             hasher.update(&chunk);
-            file.write_all(&chunk)?;
+            writer.write_all(&chunk).await?;
         }
 
         if let Some(pbv) = &pb {
             pbv.finish_with_message("Download complete");
         }
 
-        let hash_result = hasher.finalize();
-        Ok(hex::encode(hash_result))
+        Ok(total_size)
+    }
+
+    fn part_path(path: &Path) -> PathBuf {
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        PathBuf::from(part)
+    }
+
+    // Feeds the content of an existing partial download to the hasher and returns its size.
+    // Returns 0 if there is no partial download.
+    fn hash_partial_download(part_path: &Path, hasher: &mut MultiHasher) -> Result<u64> {
+        match File::open(part_path) {
+            Ok(file) => hasher.update_reader(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// Runs blocking file operations and hashing on the blocking threads of the runtime, so that
+// they don't hold up the other downloads
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::from(std::io::Error::other(e)))?
+}
+
+// Where the progress of a download is displayed
+#[derive(Clone, Copy)]
+enum Progress<'a> {
+    Hidden,
+    Bar,
+    // Downloads in a batch each have a bar
+    Multi(&'a MultiProgress),
+}
+
+impl Progress<'_> {
+    fn from_quiet(quiet: bool) -> Self {
+        if quiet {
+            Progress::Hidden
+        } else {
+            Progress::Bar
+        }
+    }
+}
+
+// Releases fetched while downloading, so that downloading several assets of a release
+// only fetches it once, even when they are downloaded concurrently.
+#[derive(Default)]
+struct ReleaseCache {
+    // Keyed by owner/repo@release
+    releases: Mutex<HashMap<String, Arc<OnceCell<GitHubRelease>>>>,
+}
+
+impl ReleaseCache {
+    async fn get(
+        &self,
+        client: &GitHubClient,
+        owner: &str,
+        repo: &str,
+        release: &ReleaseSpec,
+    ) -> Result<GitHubRelease> {
+        let key = format!("{}/{}@{}", owner, repo, release);
+        // The lock is released before awaiting, concurrent requests for the same release
+        // wait for the first one to complete.
+        let cell = self
+            .releases
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| client.find_release(owner, repo, release))
+            .await
+            .cloned()
+    }
+}
+
+// A file to download, as listed in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadRequest {
+    pub asset: AssetRef,
+    // Defaults to a file named like the asset in the current directory
+    pub output: Option<PathBuf>,
+    // Digests the file must have, eg from a lockfile
+    pub expected: Vec<(HashAlgorithm, String)>,
+    // Key the asset must have a minisign signature from, instead of the downloader's one
+    pub minisign_key: Option<MinisignKey>,
+    // Identity the asset's Sigstore bundle must have been signed by, instead of the
+    // downloader's one
+    pub sigstore_identity: Option<SigstoreIdentity>,
+}
+
+impl DownloadRequest {
+    // A request for the asset, downloaded to the current directory
+    pub fn new(asset: AssetRef) -> Self {
+        Self {
+            asset,
+            output: None,
+            expected: vec![],
+            minisign_key: None,
+            sigstore_identity: None,
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+// A minisign signature to check, with the key it must have been made with
+#[derive(Clone)]
+struct MinisignCheck {
+    // Name of the signature file
+    name: String,
+    signature: MinisignSignature,
+    key: MinisignKey,
+}
+
+impl MinisignCheck {
+    fn verified(&self) -> VerifiedSignature {
+        VerifiedSignature {
+            file: self.name[..self.name.len() - MINISIGN_SIGNATURE_EXTENSION.len()].to_string(),
+            signature: self.name.clone(),
+            signer: self.key.to_string(),
+            comment: Some(self.signature.trusted_comment().to_string()),
+        }
+    }
+}
+
+// The signatures of the asset which are verified against its content, rather than
+// against its digest
+#[derive(Default)]
+struct ContentSignatures {
+    minisign: Option<MinisignCheck>,
+    // The OpenPGP signature of the asset, with its name
+    pgp: Option<(String, PgpSignature)>,
+}
+
+impl ContentSignatures {
+    fn verifications<'a>(&'a self, keyring: Option<&'a Keyring>) -> Result<Verifications<'a>> {
+        Ok(Verifications {
+            signatures: self,
+            minisign: self
+                .minisign
+                .as_ref()
+                .map(|m| m.key.verification(&m.signature))
+                .transpose()?,
+            pgp: self
+                .pgp
+                .as_ref()
+                .zip(keyring)
+                .map(|((_, signature), keyring)| keyring.verification(signature))
+                .transpose()?,
+        })
+    }
+}
+
+// The verifications of the content signatures, fed with the content of the asset
+struct Verifications<'a> {
+    signatures: &'a ContentSignatures,
+    minisign: Option<MinisignVerification<'a>>,
+    pgp: Option<PgpVerification<'a>>,
+}
+
+impl Write for Verifications<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(minisign) = &mut self.minisign {
+            minisign.write_all(buf)?;
+        }
+        if let Some(pgp) = &mut self.pgp {
+            pgp.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Verifications<'_> {
+    // Checks the signatures of the content written, returning them with their signer
+    fn finish(self, file_name: &str) -> Result<Vec<VerifiedSignature>> {
+        let mut verified = vec![];
+        if let (Some(check), Some(verification)) = (&self.signatures.minisign, self.minisign) {
+            verification.finish(file_name)?;
+            verified.push(check.verified());
+        }
+        if let (Some((name, _)), Some(verification)) = (&self.signatures.pgp, self.pgp) {
+            verified.push(VerifiedSignature {
+                file: file_name.to_string(),
+                signature: name.clone(),
+                signer: verification.finish()?,
+                comment: None,
+            });
+        }
+        Ok(verified)
+    }
+}
+
+// Writes to a writer, feeding the data written to the content verifications too
+struct TeeWriter<'a, 'v, W> {
+    writer: &'a mut W,
+    verifications: &'a mut Verifications<'v>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for TeeWriter<'_, '_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let written = ready!(Pin::new(&mut *this.writer).poll_write(cx, buf))?;
+        this.verifications.write_all(&buf[..written])?;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_shutdown(cx)
+    }
+}
+
+// A release asset with the checksums found for it
+struct ResolvedAsset {
+    // The repository of the release
+    owner: String,
+    repo: String,
+    asset: GitHubAsset,
+    // The tag of the release, resolved if the release was designated as latest or by a
+    // version requirement
+    tag: String,
+    sources: Vec<SourceReport>,
+    // The signature published for the asset, with its name, verified once downloaded
+    signature: Option<(String, SshSignature)>,
+    // The Sigstore bundle of the asset, with its name and the identity it must have been
+    // signed by, verified once downloaded
+    sigstore: Option<(String, SigstoreBundle, SigstoreIdentity)>,
+    // The minisign and OpenPGP signatures the asset must match, verified once downloaded
+    content: ContentSignatures,
+    // The signatures verified so far
+    signatures: Vec<VerifiedSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DownloadResult {
     pub path: std::path::PathBuf,
     pub size: u64,
+    // Tag of the release the file comes from
+    pub tag: String,
+    // Name of the release asset
+    pub asset: String,
+    // URL the asset was downloaded from
+    pub url: String,
     pub algorithm: HashAlgorithm,
     pub hash: String,
+    // Digests of the file for all algorithms computed, including the one above
+    pub digests: Vec<(HashAlgorithm, String)>,
+    // The checksum sources consulted and their verification status
+    pub sources: Vec<SourceReport>,
+    // Signatures of the asset and of the checksums files used, with their signer
+    pub signatures: Vec<VerifiedSignature>,
+    // Files extracted from the downloaded archive
+    pub extracted: Vec<PathBuf>,
+}
+
+// The result of a download streamed to a writer, which has no path nor extracted files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamResult {
+    pub size: u64,
+    pub tag: String,
+    pub asset: String,
+    pub url: String,
+    pub algorithm: HashAlgorithm,
+    pub hash: String,
+    pub digests: Vec<(HashAlgorithm, String)>,
+    pub sources: Vec<SourceReport>,
+    pub signatures: Vec<VerifiedSignature>,
+}
+
+impl From<DownloadResult> for StreamResult {
+    fn from(result: DownloadResult) -> Self {
+        Self {
+            size: result.size,
+            tag: result.tag,
+            asset: result.asset,
+            url: result.url,
+            algorithm: result.algorithm,
+            hash: result.hash,
+            digests: result.digests,
+            sources: result.sources,
+            signatures: result.signatures,
+        }
+    }
 }
@@ -20,6 +20,15 @@ pub enum Error {
     #[error("Asset not found in release: {0}")]
     AssetNotFound(String),
 
+    #[error("Several assets match {selector}: {}", .candidates.join(", "))]
+    AmbiguousAsset {
+        selector: String,
+        candidates: Vec<String>,
+    },
+
+    #[error("Invalid asset selector: {0}")]
+    InvalidAssetSelector(String),
+
     #[error("Hash verification failed: expected {expected}, got {actual}")]
     HashVerificationFailed { expected: String, actual: String },
 
@@ -29,8 +38,98 @@ pub enum Error {
     #[error("Invalid URL format: {0}")]
     InvalidUrlFormat(String),
 
+    #[error("Invalid repository format, expected owner/repo[@release]: {0}")]
+    InvalidRepoFormat(String),
+
+    #[error("Invalid version requirement: {0}")]
+    InvalidVersionRequirement(String),
+
+    #[error("Release not found: {0}")]
+    ReleaseNotFound(String),
+
     #[error("GitHub API error: {0}")]
     GitHubApiError(String),
+
+    #[error("No checksum found for asset: {0}")]
+    ChecksumNotFound(String),
+
+    #[error("Checksums mirror error: {0}")]
+    MirrorError(String),
+
+    #[error("Checksums from different sources disagree: {0}")]
+    ChecksumsDisagree(String),
+
+    #[error("Invalid verification policy: {0}")]
+    InvalidPolicy(String),
+
+    #[error("Verification policy not satisfied: {0}")]
+    PolicyNotSatisfied(String),
+
+    #[error("Unsupported archive format: {0}")]
+    UnsupportedArchive(String),
+
+    #[error("Archive extraction failed: {0}")]
+    ArchiveError(String),
+
+    #[error("Archive member not found: {0}")]
+    ArchiveMemberNotFound(String),
+
+    #[error("Unsafe archive entry: {0}")]
+    UnsafeArchiveEntry(String),
+
+    #[error("Invalid manifest: {0}")]
+    ManifestError(String),
+
+    #[error("Invalid lockfile: {0}")]
+    LockfileError(String),
+
+    #[error("Manifest doesn't resolve to the locked assets: {0}")]
+    LockfileMismatch(String),
+
+    #[error("Installation failed: {0}")]
+    InstallError(String),
+
+    #[error("Invalid allowed signers file: {0}")]
+    AllowedSignersError(String),
+
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Signature not found: {0}")]
+    SignatureNotFound(String),
+
+    #[error("Signature verification failed: {0}")]
+    SignatureVerificationFailed(String),
+
+    #[error("Invalid keyring: {0}")]
+    KeyringError(String),
+
+    #[error("Unknown signer: {0}")]
+    UnknownSigner(String),
+
+    #[error("Signing key expired: {0}")]
+    KeyExpired(String),
+
+    #[error("Invalid Sigstore trust root: {0}")]
+    TrustRootError(String),
+
+    #[error("File already exists: {}", .0.display())]
+    FileExists(std::path::PathBuf),
+
+    #[error("Several files would be downloaded to {}", .0.display())]
+    DuplicateOutput(std::path::PathBuf),
+
+    #[error("{} file(s) could not be downloaded", .0)]
+    BatchFailed(usize),
+
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    #[error("{}: {source}", .path.display())]
+    FileError {
+        path: std::path::PathBuf,
+        source: Box<Error>,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,342 @@
+use crate::{Error, Result};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    Zip,
+    // A single compressed file
+    Gz,
+}
+
+impl ArchiveFormat {
+    // Guesses the format from the file name, None if it is not a supported archive
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            Some(ArchiveFormat::TarXz)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".gz") {
+            Some(ArchiveFormat::Gz)
+        } else {
+            None
+        }
+    }
+
+    // Returns true if the name has the extension of an archive or a package, supported or
+    // not, so that it is not mistaken for a binary
+    pub fn is_archive_name(name: &str) -> bool {
+        const EXTENSIONS: &[&str] = &[
+            "7z", "apk", "bz2", "deb", "dmg", "gz", "lz", "lz4", "lzma", "msi", "pkg", "rar",
+            "rpm", "tar", "tbz", "tbz2", "tgz", "txz", "tzst", "xz", "z", "zip", "zst",
+        ];
+        Self::from_name(name).is_some()
+            || Path::new(&name.to_lowercase())
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| EXTENSIONS.contains(&e))
+    }
+}
+
+// Extracts archives into a directory. Entries with absolute paths, going up the directory
+// tree or through symlinks, and symlinks pointing outside the directory, are rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extractor {
+    pub dir: PathBuf,
+    // When set, only this member is extracted, directly in dir. Members are designated by
+    // their path in the archive, or by their file name if it has no /.
+    // For .gz files, it is the name of the decompressed file.
+    pub member: Option<String>,
+}
+
+impl Extractor {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            member: None,
+        }
+    }
+
+    pub fn with_member(self, member: Option<String>) -> Self {
+        Self { member, ..self }
+    }
+
+    // Extracts the archive, whose format is guessed from name, and returns the paths of the
+    // files created. Existing files are only replaced if overwrite is true.
+    pub fn extract(&self, archive: &Path, name: &str, overwrite: bool) -> Result<Vec<PathBuf>> {
+        let format =
+            ArchiveFormat::from_name(name).ok_or_else(|| Error::UnsupportedArchive(name.into()))?;
+        fs::create_dir_all(&self.dir)?;
+        let mut extraction = Extraction {
+            extractor: self,
+            overwrite,
+            extracted: vec![],
+        };
+
+        let file = BufReader::new(File::open(archive)?);
+        match format {
+            ArchiveFormat::TarGz => extraction.tar(flate2::read::GzDecoder::new(file))?,
+            ArchiveFormat::TarXz => extraction.tar(xz2::read::XzDecoder::new(file))?,
+            ArchiveFormat::Zip => extraction.zip(file)?,
+            ArchiveFormat::Gz => {
+                // The name of the archive without .gz
+                let stem = &name[..name.len() - 3];
+                let output = self.member.as_deref().unwrap_or(stem);
+                let path = extraction.entry_path(Path::new(output))?;
+                extraction.write_file(&path, flate2::read::GzDecoder::new(file), None)?;
+            }
+        }
+
+        if extraction.extracted.is_empty() {
+            if let Some(member) = &self.member {
+                return Err(Error::ArchiveMemberNotFound(member.clone()));
+            }
+        }
+        Ok(extraction.extracted)
+    }
+}
+
+// State of an ongoing extraction
+struct Extraction<'a> {
+    extractor: &'a Extractor,
+    overwrite: bool,
+    extracted: Vec<PathBuf>,
+}
+
+impl Extraction<'_> {
+    fn tar(&mut self, reader: impl Read) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.into_owned();
+            let mode = entry.header().mode().ok();
+            let entry_type = entry.header().entry_type();
+
+            if self.extractor.member.is_some() {
+                if entry_type.is_file() && self.is_member(&name)? {
+                    let path = self.member_path(&name)?;
+                    self.write_file(&path, &mut entry, mode)?;
+                }
+                continue;
+            }
+
+            let path = self.entry_path(&name)?;
+            match entry_type {
+                tar::EntryType::Directory => fs::create_dir_all(&path)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    self.write_file(&path, &mut entry, mode)?
+                }
+                tar::EntryType::Symlink => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| Error::UnsafeArchiveEntry(name.display().to_string()))?;
+                    self.symlink(&name, &path, &target)?;
+                }
+                tar::EntryType::Link => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| Error::UnsafeArchiveEntry(name.display().to_string()))?;
+                    let target = self.entry_path(&target)?;
+                    self.check_overwrite(&path)?;
+                    fs::hard_link(target, &path)?;
+                    self.extracted.push(path);
+                }
+                // Devices, fifos, ... are not needed to install software
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn zip(&mut self, file: BufReader<File>) -> Result<()> {
+        let mut archive =
+            zip::ZipArchive::new(file).map_err(|e| Error::ArchiveError(e.to_string()))?;
+        for i in 0..archive.len() {
+            let mut entry = archive
+                .by_index(i)
+                .map_err(|e| Error::ArchiveError(e.to_string()))?;
+            // enclosed_name rejects absolute paths and paths going up, which are checked
+            // again by entry_path
+            let name = entry
+                .enclosed_name()
+                .ok_or_else(|| Error::UnsafeArchiveEntry(entry.name().to_string()))?;
+            let mode = entry.unix_mode();
+
+            if self.extractor.member.is_some() {
+                if entry.is_file() && !entry.is_symlink() && self.is_member(&name)? {
+                    let path = self.member_path(&name)?;
+                    self.write_file(&path, &mut entry, mode)?;
+                }
+                continue;
+            }
+
+            let path = self.entry_path(&name)?;
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+            } else if entry.is_symlink() {
+                let mut target = String::new();
+                entry.read_to_string(&mut target)?;
+                self.symlink(&name, &path, Path::new(&target))?;
+            } else {
+                self.write_file(&path, &mut entry, mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns true if the entry is the member to extract, failing if another entry matching
+    // it was already extracted.
+    fn is_member(&self, name: &Path) -> Result<bool> {
+        let Some(member) = self.extractor.member.as_deref() else {
+            return Ok(false);
+        };
+        let name = name.strip_prefix(".").unwrap_or(name);
+        let matches = if member.contains('/') {
+            name == Path::new(member.trim_start_matches("./"))
+        } else {
+            name.file_name() == Some(member.as_ref())
+        };
+        if matches && !self.extracted.is_empty() {
+            return Err(Error::ArchiveError(format!(
+                "several entries match member {}",
+                member
+            )));
+        }
+        Ok(matches)
+    }
+
+    // The member is extracted directly in the target directory
+    fn member_path(&self, name: &Path) -> Result<PathBuf> {
+        let file_name = name
+            .file_name()
+            .ok_or_else(|| Error::UnsafeArchiveEntry(name.display().to_string()))?;
+        self.entry_path(Path::new(file_name))
+    }
+
+    // Returns the path where the entry named name is extracted, rejecting names which are
+    // absolute, go up the directory tree, or go through a symlink created by the archive.
+    fn entry_path(&self, name: &Path) -> Result<PathBuf> {
+        let unsafe_entry = || Error::UnsafeArchiveEntry(name.display().to_string());
+        let mut path = self.extractor.dir.clone();
+        for component in name.components() {
+            match component {
+                Component::Normal(part) => {
+                    if path != self.extractor.dir
+                        && fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink())
+                    {
+                        return Err(unsafe_entry());
+                    }
+                    path.push(part)
+                }
+                Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(unsafe_entry())
+                }
+            }
+        }
+        if path == self.extractor.dir {
+            return Err(unsafe_entry());
+        }
+        Ok(path)
+    }
+
+    fn check_overwrite(&self, path: &Path) -> Result<()> {
+        if fs::symlink_metadata(path).is_ok() {
+            if !self.overwrite {
+                return Err(Error::FileExists(path.to_path_buf()));
+            }
+            // Removing the file first ensures we don't write through a symlink
+            if path.is_dir() && !path.is_symlink() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &Path, mut reader: impl Read, mode: Option<u32>) -> Result<()> {
+        self.check_overwrite(path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(path)?;
+        io::copy(&mut reader, &mut file)?;
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            // Only keep the permission bits, not setuid and the like
+            file.set_permissions(fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        self.extracted.push(path.to_path_buf());
+        Ok(())
+    }
+
+    // Creates the symlink at path, if its target is inside the target directory
+    fn symlink(&mut self, name: &Path, path: &Path, target: &Path) -> Result<()> {
+        let unsafe_entry =
+            || Error::UnsafeArchiveEntry(format!("{} -> {}", name.display(), target.display()));
+        // Resolve the target relative to the directory of the link. Symlinks in the target
+        // are only followed by the OS, and as they are checked when created, they point inside
+        // the directory. But going up from a symlink can escape it, which is rejected, as is
+        // going up from a path which doesn't exist yet, as it could later be a symlink.
+        let parent = name.parent().unwrap_or(Path::new(""));
+        let mut resolved = self
+            .entry_path(parent)
+            .unwrap_or(self.extractor.dir.clone());
+        let mut depth = parent
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .count();
+        for component in target.components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    depth += 1;
+                }
+                Component::CurDir => {}
+                Component::ParentDir
+                    if depth > 0 && fs::symlink_metadata(&resolved).is_ok_and(|m| m.is_dir()) =>
+                {
+                    resolved.pop();
+                    depth -= 1;
+                }
+                _ => return Err(unsafe_entry()),
+            }
+        }
+
+        // Links created before may go up from this directory
+        if fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()) {
+            return Err(unsafe_entry());
+        }
+        self.check_overwrite(path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        create_symlink(target, path)?;
+        self.extracted.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, path: &Path) -> Result<()> {
+    Ok(std::os::unix::fs::symlink(target, path)?)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(target: &Path, path: &Path) -> Result<()> {
+    Err(Error::ArchiveError(format!(
+        "symlinks are not supported on this platform: {} -> {}",
+        path.display(),
+        target.display()
+    )))
+}
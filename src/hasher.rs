@@ -1,19 +1,64 @@
 use crate::{Error, Result};
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384, Sha512};
 use std::io::Read;
 use std::path::Path;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Size of the buffer in which files are read to be hashed
+pub const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HashAlgorithm {
     Sha256,
+    Sha384,
+    Sha512,
+    Blake3,
+    // Weak algorithms, only used when explicitly allowed
+    Sha1,
+    Md5,
+}
+
+impl HashAlgorithm {
+    // Guesses the algorithm from the length of an hex encoded hash, for
+    // checksums files not specifying the algorithm.
+    // Blake3 hashes have the same length as Sha256 ones and are never guessed.
+    pub fn from_hex_length(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(HashAlgorithm::Md5),
+            40 => Some(HashAlgorithm::Sha1),
+            64 => Some(HashAlgorithm::Sha256),
+            96 => Some(HashAlgorithm::Sha384),
+            128 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn hex_length(&self) -> usize {
+        match self {
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+            HashAlgorithm::Sha384 => 96,
+            HashAlgorithm::Sha512 => 128,
+        }
+    }
+
+    // Algorithms for which collisions can be computed, and which must not be trusted by default
+    pub fn is_weak(&self) -> bool {
+        matches!(self, HashAlgorithm::Sha1 | HashAlgorithm::Md5)
+    }
 }
 
 impl std::str::FromStr for HashAlgorithm {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        match s.to_lowercase().as_str() {
+        match s.to_lowercase().replace('-', "").as_str() {
             "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha384" => Ok(HashAlgorithm::Sha384),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "md5" => Ok(HashAlgorithm::Md5),
             _ => Err(Error::UnsupportedAlgorithm(s.to_string())),
         }
     }
@@ -23,31 +68,154 @@ impl std::fmt::Display for HashAlgorithm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Sha384 => write!(f, "sha384"),
+            HashAlgorithm::Sha512 => write!(f, "sha512"),
+            HashAlgorithm::Blake3 => write!(f, "blake3"),
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Md5 => write!(f, "md5"),
+        }
+    }
+}
+
+// Hasher state of any supported algorithm, fed with data as it is received
+pub enum IncrementalHasher {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+    Sha1(sha1::Sha1),
+    Md5(md5::Md5),
+}
+
+impl IncrementalHasher {
+    pub fn new(algorithm: &HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => IncrementalHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha384 => IncrementalHasher::Sha384(Sha384::new()),
+            HashAlgorithm::Sha512 => IncrementalHasher::Sha512(Sha512::new()),
+            HashAlgorithm::Blake3 => IncrementalHasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha1 => IncrementalHasher::Sha1(sha1::Sha1::new()),
+            HashAlgorithm::Md5 => IncrementalHasher::Md5(md5::Md5::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            IncrementalHasher::Sha256(h) => h.update(data),
+            IncrementalHasher::Sha384(h) => h.update(data),
+            IncrementalHasher::Sha512(h) => h.update(data),
+            IncrementalHasher::Blake3(h) => {
+                h.update(data);
+            }
+            IncrementalHasher::Sha1(h) => h.update(data),
+            IncrementalHasher::Md5(h) => h.update(data),
+        }
+    }
+
+    // Feeds all data of the reader to the hasher and returns its size
+    pub fn update_reader<R: Read>(&mut self, reader: R) -> Result<u64> {
+        read_chunks(reader, |chunk| self.update(chunk))
+    }
+
+    // Returns the hex encoded hash
+    pub fn finalize(self) -> String {
+        match self {
+            IncrementalHasher::Sha256(h) => hex::encode(h.finalize()),
+            IncrementalHasher::Sha384(h) => hex::encode(h.finalize()),
+            IncrementalHasher::Sha512(h) => hex::encode(h.finalize()),
+            IncrementalHasher::Blake3(h) => h.finalize().to_hex().to_string(),
+            IncrementalHasher::Sha1(h) => hex::encode(h.finalize()),
+            IncrementalHasher::Md5(h) => hex::encode(h.finalize()),
         }
     }
 }
 
+// Feeds the same data to hashers of several algorithms, to compute all digests in one pass
+pub struct MultiHasher {
+    hashers: Vec<(HashAlgorithm, IncrementalHasher)>,
+}
+
+impl MultiHasher {
+    // Duplicate algorithms are only computed once
+    pub fn new(algorithms: &[HashAlgorithm]) -> Self {
+        let mut hashers: Vec<(HashAlgorithm, IncrementalHasher)> = vec![];
+        for algorithm in algorithms {
+            if !hashers.iter().any(|(a, _)| a == algorithm) {
+                hashers.push((algorithm.clone(), IncrementalHasher::new(algorithm)));
+            }
+        }
+        Self { hashers }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in self.hashers.iter_mut() {
+            hasher.update(data);
+        }
+    }
+
+    // Feeds all data of the reader to the hashers and returns its size
+    pub fn update_reader<R: Read>(&mut self, reader: R) -> Result<u64> {
+        read_chunks(reader, |chunk| self.update(chunk))
+    }
+
+    // Returns the hex encoded hash of each algorithm, in the order they were given
+    pub fn finalize(self) -> Vec<(HashAlgorithm, String)> {
+        self.hashers
+            .into_iter()
+            .map(|(algorithm, hasher)| (algorithm, hasher.finalize()))
+            .collect()
+    }
+}
+
+// Reads the reader in chunks of HASH_BUFFER_SIZE passed to f, returning the size read
+fn read_chunks<R: Read>(mut reader: R, mut f: impl FnMut(&[u8])) -> Result<u64> {
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    let mut size = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        f(&buffer[..n]);
+        size += n as u64;
+    }
+    Ok(size)
+}
+
 pub struct Hasher;
 
 impl Hasher {
+    // The file is hashed in chunks, so that its size doesn't impact memory usage
     pub fn compute_file_hash<P: AsRef<Path>>(path: P, algorithm: &HashAlgorithm) -> Result<String> {
-        let mut file = std::fs::File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        let file = std::fs::File::open(path)?;
+        Self::compute_reader_hash(file, algorithm)
+    }
 
-        Self::compute_hash(&buffer, algorithm)
+    // Hashes the file by mapping it in memory, which can be faster for big files.
+    // The file must not be modified while it is hashed.
+    #[cfg(feature = "mmap")]
+    pub fn compute_file_hash_mmap<P: AsRef<Path>>(
+        path: P,
+        algorithm: &HashAlgorithm,
+    ) -> Result<String> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the file is only read, and the caller guarantees it is not modified concurrently
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::compute_hash(&mmap, algorithm)
     }
 
-    pub fn compute_hash(data: &[u8], algorithm: &HashAlgorithm) -> Result<String> {
-        let hash = match algorithm {
-            HashAlgorithm::Sha256 => {
-                let mut hasher = Sha256::new();
-                hasher.update(data);
-                hasher.finalize()
-            }
-        };
+    pub fn compute_reader_hash<R: Read>(reader: R, algorithm: &HashAlgorithm) -> Result<String> {
+        let mut hasher = IncrementalHasher::new(algorithm);
+        hasher.update_reader(reader)?;
+        Ok(hasher.finalize())
+    }
 
-        Ok(hex::encode(hash))
+    pub fn compute_hash(data: &[u8], algorithm: &HashAlgorithm) -> Result<String> {
+        let mut hasher = IncrementalHasher::new(algorithm);
+        hasher.update(data);
+        Ok(hasher.finalize())
     }
 
     pub fn parse_digest(digest: &str) -> Result<(HashAlgorithm, String)> {
@@ -59,8 +227,16 @@ impl Hasher {
             )));
         }
 
-        let algorithm = parts[0].parse()?;
-        let hash_value = parts[1].to_string();
+        let algorithm: HashAlgorithm = parts[0].parse()?;
+        let hash_value = parts[1].to_lowercase();
+        if hash_value.len() != algorithm.hex_length()
+            || !hash_value.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(Error::HashError(format!(
+                "Invalid {} hash: {}",
+                algorithm, parts[1]
+            )));
+        }
 
         Ok((algorithm, hash_value))
     }
@@ -0,0 +1,183 @@
+use crate::{
+    client::AssetRef,
+    downloader::{DownloadResult, Downloader},
+    extract::{ArchiveFormat, Extractor},
+    Error, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Name of the file recording installed binaries, in the asfald data directory
+const REGISTRY_FILE_NAME: &str = "installed.json";
+
+// A binary installed by asfald, as recorded in the registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledBinary {
+    pub name: String,
+    pub path: PathBuf,
+    pub owner: String,
+    pub repo: String,
+    pub tag: String,
+    pub asset: String,
+    // Verified digest of the downloaded asset, as <algorithm>:<hash>
+    pub digest: String,
+}
+
+// Installs binaries from release assets: the asset is downloaded and verified, the binary
+// is extracted if the asset is an archive, made executable and moved to the bin directory.
+pub struct Installer {
+    pub bin_dir: PathBuf,
+    // File recording the installed binaries
+    pub registry: PathBuf,
+    // Name of the binary in the archive, defaults to the installed name
+    pub binary: Option<String>,
+    // Name of the installed binary, defaults to the repository name
+    pub name: Option<String>,
+}
+
+impl Installer {
+    // Installs to ~/.local/bin, recording installed binaries in the user's data directory,
+    // eg ~/.local/share/asfald/installed.json on Linux
+    pub fn new() -> Result<Self> {
+        let home = dirs::home_dir()
+            .ok_or_else(|| Error::InstallError("cannot find the home directory".to_string()))?;
+        let data_dir = dirs::data_dir().unwrap_or_else(|| home.join(".local/share"));
+        Ok(Self {
+            bin_dir: home.join(".local/bin"),
+            registry: data_dir.join("asfald").join(REGISTRY_FILE_NAME),
+            binary: None,
+            name: None,
+        })
+    }
+
+    pub fn with_bin_dir(self, bin_dir: PathBuf) -> Self {
+        Self { bin_dir, ..self }
+    }
+
+    pub fn with_registry(self, registry: PathBuf) -> Self {
+        Self { registry, ..self }
+    }
+
+    pub fn with_binary(self, binary: Option<String>) -> Self {
+        Self { binary, ..self }
+    }
+
+    pub fn with_name(self, name: Option<String>) -> Self {
+        Self { name, ..self }
+    }
+
+    // Installs the binary from the asset, replacing any binary with the same name in the bin
+    // directory. The binary is only moved there once verified, so that an existing binary is
+    // left untouched if anything fails.
+    pub async fn install(
+        &self,
+        downloader: &Downloader,
+        asset_ref: &AssetRef,
+        quiet: bool,
+    ) -> Result<(InstalledBinary, DownloadResult)> {
+        let name = self.name.clone().unwrap_or_else(|| asset_ref.repo.clone());
+        // The binary must be installed directly in the bin directory
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(Error::InstallError(format!("invalid binary name {}", name)));
+        }
+        fs::create_dir_all(&self.bin_dir)?;
+        // Work next to the destination, so that the binary can be renamed in place atomically
+        let work_dir =
+            self.bin_dir
+                .join(format!(".asfald-install-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&work_dir)?;
+        let result = self
+            .install_from(downloader, asset_ref, &name, &work_dir, quiet)
+            .await;
+        let _ = fs::remove_dir_all(&work_dir);
+        let (installed, download) = result?;
+
+        self.record(&installed)?;
+        Ok((installed, download))
+    }
+
+    async fn install_from(
+        &self,
+        downloader: &Downloader,
+        asset_ref: &AssetRef,
+        name: &str,
+        work_dir: &Path,
+        quiet: bool,
+    ) -> Result<(InstalledBinary, DownloadResult)> {
+        // The downloaded file is named after the asset, for the archive format to be detected
+        let download_dir = work_dir.join("download");
+        fs::create_dir_all(&download_dir)?;
+        let download = downloader
+            .download_asset(asset_ref, Some(&download_dir.join("asset")), quiet)
+            .await?;
+        let downloaded = download_dir.join(&download.asset);
+        fs::rename(download_dir.join("asset"), &downloaded)?;
+
+        // Assets are only installed as is if they don't look like an archive, so that an
+        // archive we cannot extract is not installed as the binary
+        let binary = match ArchiveFormat::from_name(&download.asset) {
+            Some(_) => {
+                let member = self.binary.clone().unwrap_or_else(|| name.to_string());
+                let extracted = Extractor::new(work_dir.join("extract"))
+                    .with_member(Some(member))
+                    .extract(&downloaded, &download.asset, false)?;
+                // Only one file is extracted when selecting a member
+                extracted[0].clone()
+            }
+            None if ArchiveFormat::is_archive_name(&download.asset) => {
+                return Err(Error::UnsupportedArchive(download.asset.clone()))
+            }
+            None => downloaded,
+        };
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))?;
+        }
+
+        let path = self.bin_dir.join(name);
+        fs::rename(&binary, &path)?;
+
+        let installed = InstalledBinary {
+            name: name.to_string(),
+            path,
+            owner: asset_ref.owner.clone(),
+            repo: asset_ref.repo.clone(),
+            tag: download.tag.clone(),
+            asset: download.asset.clone(),
+            digest: format!("{}:{}", download.algorithm, download.hash),
+        };
+        Ok((installed, download))
+    }
+
+    // Returns the binaries installed, as recorded in the registry
+    pub fn installed(&self) -> Result<Vec<InstalledBinary>> {
+        match fs::read_to_string(&self.registry) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Adds the binary to the registry, replacing the entry for the same path. The registry
+    // is locked while it is updated, so that concurrent installations don't lose entries.
+    fn record(&self, binary: &InstalledBinary) -> Result<()> {
+        if let Some(parent) = self.registry.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock = fs::File::create(self.registry.with_extension("json.lock"))?;
+        lock.lock()?;
+
+        let mut installed = self.installed()?;
+        installed.retain(|b| b.path != binary.path);
+        installed.push(binary.clone());
+        installed.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let tmp = self.registry.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&installed)?)?;
+        fs::rename(&tmp, &self.registry)?;
+        Ok(())
+    }
+}
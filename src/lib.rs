@@ -1,11 +1,39 @@
+pub mod attestation;
+pub mod checksums;
 pub mod cli;
 pub mod client;
 pub mod downloader;
 pub mod error;
+pub mod extract;
 pub mod hasher;
+pub mod install;
+pub mod lockfile;
+pub mod manifest;
+pub mod mirror;
+pub mod openpgp;
+pub mod policy;
+pub mod selector;
+pub mod signature;
+pub mod sigstore;
 
+pub use attestation::AttestationPolicy;
 pub use cli::Cli;
-pub use client::{GitHubAsset, GitHubClient, GitHubRelease};
-pub use downloader::{DownloadResult, Downloader};
+pub use client::{AssetRef, GitHubAsset, GitHubClient, GitHubRelease, ReleaseSpec};
+pub use downloader::{DownloadRequest, DownloadResult, Downloader, StreamResult};
 pub use error::{Error, Result};
-pub use hasher::{HashAlgorithm, Hasher};
+pub use extract::{ArchiveFormat, Extractor};
+pub use hasher::{HashAlgorithm, Hasher, IncrementalHasher, MultiHasher};
+pub use install::{InstalledBinary, Installer};
+pub use lockfile::{LockedAsset, Lockfile};
+pub use manifest::{Manifest, ManifestEntry};
+pub use mirror::ChecksumsMirror;
+pub use openpgp::{Keyring, PgpSignature};
+pub use policy::{ChecksumSource, SourceReport, SourceStatus, VerificationPolicy};
+pub use selector::{AssetSelector, Platform};
+pub use signature::{
+    AllowedSigners, MinisignKey, MinisignSignature, SshSignature, VerifiedSignature,
+};
+pub use sigstore::{
+    BuildClaims, InTotoStatement, SigstoreBundle, SigstoreIdentity, SigstoreSigner,
+    SigstoreTrustRoot, VerifiedBundle,
+};
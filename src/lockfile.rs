@@ -0,0 +1,215 @@
+use crate::{
+    client::{AssetRef, ReleaseSpec},
+    downloader::{DownloadRequest, DownloadResult, Downloader},
+    hasher::Hasher,
+    selector::AssetSelector,
+    Error, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const LOCKFILE_HEADER: &str = "# Generated by asfald, do not edit\n\n";
+
+// The assets resolved for the entries of a manifest, with their digests. Entries selecting
+// the asset built for the platform (auto) are locked for each platform they were resolved
+// on, so that a lockfile can be shared between platforms. Eg
+//
+// [[asset]]
+// source = "jesseduffield/lazygit@^0.44 glob:lazygit_*_Linux_x86_64.tar.gz"
+// owner = "jesseduffield"
+// repo = "lazygit"
+// tag = "v0.44.1"
+// asset = "lazygit_0.44.1_Linux_x86_64.tar.gz"
+// url = "https://github.com/jesseduffield/lazygit/releases/download/v0.44.1/lazygit_0.44.1_Linux_x86_64.tar.gz"
+// digest = "sha256:84682f4ad5a449d0a3ffbc8332200fe8651aee9dd91dcd8d87197ba6c2450dbc"
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub asset: Vec<LockedAsset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedAsset {
+    // The manifest entry the asset was resolved for, as repository, release and selector
+    // as written in the manifest
+    pub source: String,
+    // The platform the asset was selected for, for auto selectors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    pub owner: String,
+    pub repo: String,
+    pub tag: String,
+    pub asset: String,
+    pub url: String,
+    // As <algorithm>:<hash>
+    pub digest: String,
+}
+
+impl std::str::FromStr for Lockfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| Error::LockfileError(e.to_string()))
+    }
+}
+
+impl std::fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let content = toml::to_string(self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}{}", LOCKFILE_HEADER, content)
+    }
+}
+
+// The manifest entry of the asset, and the platform it is selected for if auto. The
+// platform is left out of the entry, so that it is the same on all platforms.
+fn source(asset_ref: &AssetRef) -> (String, Option<String>) {
+    match &asset_ref.asset {
+        AssetSelector::Platform(platform) => (
+            format!(
+                "{}/{}@{} auto",
+                asset_ref.owner, asset_ref.repo, asset_ref.release
+            ),
+            Some(platform.to_string()),
+        ),
+        _ => (asset_ref.to_string(), None),
+    }
+}
+
+impl Lockfile {
+    // The lockfile of a manifest, eg tools.lock for tools.toml
+    pub fn path_for(manifest: &Path) -> PathBuf {
+        manifest.with_extension("lock")
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("lock.tmp");
+        std::fs::write(&tmp, self.to_string())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    // Records the assets downloaded for the requests
+    pub fn from_results(requests: &[DownloadRequest], results: &[DownloadResult]) -> Self {
+        let asset = requests
+            .iter()
+            .zip(results)
+            .map(|(request, result)| {
+                let (source, platform) = source(&request.asset);
+                LockedAsset {
+                    source,
+                    platform,
+                    owner: request.asset.owner.clone(),
+                    repo: request.asset.repo.clone(),
+                    tag: result.tag.clone(),
+                    asset: result.asset.clone(),
+                    url: result.url.clone(),
+                    digest: format!("{}:{}", result.algorithm, result.hash),
+                }
+            })
+            .collect();
+        Self { asset }
+    }
+
+    // Keeps the assets locked in previous for other platforms, for the entries still locked
+    pub fn with_other_platforms(self, previous: &Lockfile) -> Self {
+        let others: Vec<LockedAsset> = previous
+            .asset
+            .iter()
+            .filter(|old| {
+                old.platform.is_some()
+                    && self.asset.iter().any(|new| new.source == old.source)
+                    && !self
+                        .asset
+                        .iter()
+                        .any(|new| (&new.source, &new.platform) == (&old.source, &old.platform))
+            })
+            .cloned()
+            .collect();
+        let mut asset = self.asset;
+        asset.extend(others);
+        Self { asset }
+    }
+
+    // Checks that the requests still resolve to the locked assets, and returns requests
+    // for exactly these assets, pinning their locked digests.
+    pub async fn locked_requests(
+        &self,
+        downloader: &Downloader,
+        requests: &[DownloadRequest],
+    ) -> Result<Vec<DownloadRequest>> {
+        let sources: Vec<(String, Option<String>)> =
+            requests.iter().map(|r| source(&r.asset)).collect();
+        if let Some(locked) = self
+            .asset
+            .iter()
+            .find(|l| !sources.iter().any(|(source, _)| *source == l.source))
+        {
+            return Err(Error::LockfileMismatch(format!(
+                "{} is locked but not in the manifest",
+                locked.source
+            )));
+        }
+
+        let locked: Vec<&LockedAsset> = sources
+            .iter()
+            .map(|(source, platform)| {
+                self.asset
+                    .iter()
+                    .find(|l| (&l.source, &l.platform) == (source, platform))
+                    .ok_or_else(|| match platform {
+                        Some(platform) => Error::LockfileMismatch(format!(
+                            "{} is not locked for {}",
+                            source, platform
+                        )),
+                        None => Error::LockfileMismatch(format!("{} is not locked", source)),
+                    })
+            })
+            .collect::<Result<_>>()?;
+
+        // The entries are resolved concurrently, each release being only fetched once
+        let asset_refs: Vec<AssetRef> = requests.iter().map(|r| r.asset.clone()).collect();
+        let resolved = downloader.resolve_all(&asset_refs).await;
+        let mut locked_requests = vec![];
+        for (((request, (source, _)), locked), resolved) in
+            requests.iter().zip(&sources).zip(locked).zip(resolved)
+        {
+            let (tag, asset) = resolved?;
+            if (&tag, &asset.name, &asset.browser_download_url)
+                != (&locked.tag, &locked.asset, &locked.url)
+            {
+                return Err(Error::LockfileMismatch(format!(
+                    "{} resolves to {} {} ({}), {} {} ({}) is locked",
+                    source,
+                    tag,
+                    asset.name,
+                    asset.browser_download_url,
+                    locked.tag,
+                    locked.asset,
+                    locked.url
+                )));
+            }
+
+            let digest = Hasher::parse_digest(&locked.digest)
+                .map_err(|e| Error::LockfileError(format!("{}: {}", source, e)))?;
+            locked_requests.push(DownloadRequest {
+                asset: AssetRef {
+                    owner: locked.owner.clone(),
+                    repo: locked.repo.clone(),
+                    release: ReleaseSpec::Tag(locked.tag.clone()),
+                    asset: AssetSelector::Name(locked.asset.clone()),
+                },
+                output: request.output.clone(),
+                expected: vec![digest],
+                minisign_key: request.minisign_key.clone(),
+                sigstore_identity: request.sigstore_identity.clone(),
+            });
+        }
+        Ok(locked_requests)
+    }
+}
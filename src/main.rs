@@ -1,33 +1,433 @@
-use asfald::{cli::Cli, downloader::Downloader, error::Result};
+use asfald::{
+    cli::{
+        BatchArgs, Cli, Command, DownloadArgs, InstallArgs, InstalledArgs, ListArgs,
+        VerificationArgs, VerifyArgs,
+    },
+    downloader::Downloader,
+    error::Result,
+    AllowedSigners, AssetRef, AssetSelector, AttestationPolicy, ChecksumsMirror, DownloadResult,
+    Error, Extractor, GitHubClient, Hasher, Installer, Keyring, Lockfile, Manifest,
+    SigstoreIdentity, SigstoreTrustRoot, SourceStatus, StreamResult,
+};
 use clap::Parser;
+use std::path::{Path, PathBuf};
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    let downloader = Downloader::new();
-    let output_path = cli.output.as_deref();
-
-    match downloader
-        .download_and_verify(cli.url, output_path, cli.quiet)
-        .await
-    {
-        Ok(result) => {
-            if cli.verbose {
-                println!("Successfully downloaded and verified file:");
-                println!("  Path: {}", result.path.display());
-                println!("  Size: {} bytes", result.size);
-                println!("  Algorithm: {}", result.algorithm);
-                println!("  Hash: {}", result.hash);
-            }
+async fn main() {
+    let command = Cli::parse().command();
+    let quiet = command.quiet();
+
+    let result = match command {
+        Command::Download(args) => download(*args).await,
+        Command::Verify(args) => verify(*args).await,
+        Command::List(args) => list(args).await,
+        Command::Install(args) => install(*args).await,
+        Command::Installed(args) => installed(args),
+        Command::Batch(args) => batch(*args).await,
+    };
+    if let Err(err) = result {
+        fail(err, quiet);
+    }
+}
+
+// Reports the error that made the command fail, and exits
+fn fail(err: Error, quiet: bool) -> ! {
+    if !quiet {
+        eprintln!("Error: {}", err);
+    }
+    std::process::exit(1)
+}
+
+// Adds the path of the file that couldn't be read or written to its error
+fn in_file<T>(path: &Path, result: Result<T>) -> Result<T> {
+    result.map_err(|e| Error::FileError {
+        path: path.to_path_buf(),
+        source: Box::new(e),
+    })
+}
+
+fn downloader(args: &VerificationArgs) -> Result<Downloader> {
+    let mut downloader = Downloader::new()
+        .with_client(GitHubClient::new().with_api_key(args.token.clone()))
+        .with_policy(args.policy.clone())
+        .with_weak_algorithms(args.allow_weak_algorithms)
+        .with_algorithms(args.digests.clone())
+        .with_expected(args.expected.clone())
+        .with_signature_namespace(args.signature_namespace.clone());
+    if args.mirror {
+        downloader = downloader.with_mirror(ChecksumsMirror::new(args.mirror_url.clone()));
+    }
+    if let Some(key) = &args.minisign_key {
+        downloader = downloader.with_minisign_key(key.clone());
+    }
+    if let Some(path) = &args.allowed_signers {
+        downloader =
+            downloader.with_allowed_signers(in_file(path, AllowedSigners::from_file(path))?);
+    }
+    if let Some(path) = &args.keyring {
+        downloader = downloader.with_keyring(in_file(path, Keyring::from_file(path))?);
+    }
+    if let Some(path) = &args.sigstore_trust_root {
+        downloader =
+            downloader.with_sigstore_trust_root(in_file(path, SigstoreTrustRoot::from_file(path))?);
+    }
+    if let (Some(identity), Some(issuer)) = (
+        &args.certificate_identity_regexp,
+        &args.certificate_oidc_issuer_regexp,
+    ) {
+        downloader = downloader.with_sigstore_identity(SigstoreIdentity::new(identity, issuer)?);
+    }
+    if args.require_attestation {
+        let mut policy =
+            AttestationPolicy::new().with_predicate_type(args.attestation_predicate_type.clone());
+        if let Some(repository) = &args.attestation_repo {
+            policy = policy.with_repository(repository.clone());
+        }
+        if let Some(workflow) = &args.attestation_signer_workflow {
+            policy = policy.with_signer_workflow(workflow.clone());
+        }
+        if let Some(source_ref) = &args.attestation_source_ref {
+            policy = policy.with_source_ref(source_ref.clone());
         }
-        Err(e) => {
-            if !cli.quiet {
-                eprintln!("Error: {}", e);
+        downloader = downloader.with_attestation_policy(policy);
+    }
+    Ok(downloader)
+}
+
+async fn download(args: DownloadArgs) -> Result<()> {
+    let verification = &args.verification;
+    let downloader = downloader(verification)?
+        .with_resume(!args.no_resume)
+        .with_overwrite(args.overwrite);
+    // Required by the argument parser
+    let target = args.target.expect("missing URL");
+    let asset_ref = asset_ref(&target, args.asset)?;
+    let output_path = args.output.as_deref();
+    // "-" as output path streams the file to stdout
+    let to_stdout = output_path == Some(Path::new("-"));
+    if to_stdout && args.extract.is_some() {
+        return Err(Error::InvalidArguments(
+            "archives written to stdout cannot be extracted".to_string(),
+        ));
+    }
+    let downloader = match args.extract {
+        Some(dir) => downloader.with_extractor(Extractor::new(dir).with_member(args.member)),
+        None => downloader,
+    };
+
+    let (path, result, extracted) = if to_stdout {
+        let mut stdout = tokio::io::BufWriter::new(tokio::io::stdout());
+        // The progress bar is not displayed, so it doesn't mix with the file content
+        downloader
+            .download_asset_to_writer(&asset_ref, &mut stdout, true)
+            .await
+            .inspect_err(|_| {
+                if !verification.quiet {
+                    eprintln!("The data written to stdout must not be used!");
+                }
+            })
+            .map(|result| (None, result, vec![]))?
+    } else {
+        downloader
+            .download_asset(&asset_ref, output_path, verification.quiet)
+            .await
+            .map(|result| {
+                let (path, extracted) = (result.path.clone(), result.extracted.clone());
+                (Some(path), result.into(), extracted)
+            })?
+    };
+
+    if verification.verbose {
+        print_result(
+            "downloaded and verified",
+            path.as_deref(),
+            &result,
+            &extracted,
+            to_stdout,
+        );
+    }
+    Ok(())
+}
+
+// Parses the target of a download, either the URL of a release file, or a repository
+// from which the asset is selected
+fn asset_ref(target: &str, asset: Option<AssetSelector>) -> Result<AssetRef> {
+    if target.contains("://") {
+        if let Some(asset) = asset {
+            return Err(Error::InvalidAssetSelector(format!(
+                "{} cannot be used with a URL",
+                asset
+            )));
+        }
+        return GitHubClient::parse_release_url(target);
+    }
+    let (owner, repo, release) = GitHubClient::parse_repo(target)?;
+    Ok(AssetRef {
+        owner,
+        repo,
+        release,
+        asset: asset.unwrap_or_else(AssetSelector::auto),
+    })
+}
+
+async fn verify(args: VerifyArgs) -> Result<()> {
+    let verification = &args.verification;
+    let result = downloader(verification)?
+        .verify_file(args.url, &args.file)
+        .await?;
+    if verification.verbose {
+        print_download_result("verified", &result);
+    }
+    Ok(())
+}
+
+async fn batch(args: BatchArgs) -> Result<()> {
+    let verification = &args.verification;
+    if !verification.expected.is_empty() {
+        return Err(Error::InvalidArguments(
+            "--expect cannot be used with batch, use expect in the manifest entries".to_string(),
+        ));
+    }
+    let lockfile_path = args
+        .lockfile
+        .clone()
+        .unwrap_or_else(|| Lockfile::path_for(&args.manifest));
+    let requests = in_file(
+        &args.manifest,
+        Manifest::from_file(&args.manifest).and_then(|m| m.requests()),
+    )?;
+    let downloader = downloader(verification)?
+        .with_resume(!args.no_resume)
+        .with_overwrite(args.overwrite)
+        .with_concurrency(args.jobs);
+
+    // In locked mode, the locked assets are downloaded and must match the locked digests,
+    // which are pinned, so they are checked whatever the policy
+    let requests = if args.locked {
+        in_file(&lockfile_path, Lockfile::from_file(&lockfile_path))?
+            .locked_requests(&downloader, &requests)
+            .await?
+    } else {
+        requests
+    };
+
+    let results = downloader.download_all(&requests, verification.quiet).await;
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    if !verification.quiet {
+        for (request, result) in requests.iter().zip(&results) {
+            match result {
+                Ok(result) => println!("OK      {} -> {}", request.asset, result.path.display()),
+                Err(e) => println!("FAILED  {}: {}", request.asset, e),
             }
-            std::process::exit(1);
         }
+        println!(
+            "{} file(s) downloaded and verified, {} failed",
+            results.len() - failed,
+            failed
+        );
+    }
+    if verification.verbose {
+        for result in results.iter().flatten() {
+            print_download_result("downloaded and verified", result);
+        }
+    }
+    if failed > 0 {
+        return Err(Error::BatchFailed(failed));
+    }
+
+    // The lockfile is only updated when all entries could be resolved. The assets locked
+    // for other platforms are kept.
+    if !args.locked {
+        let results: Vec<DownloadResult> = results.into_iter().flatten().collect();
+        let previous = Lockfile::from_file(&lockfile_path).unwrap_or_default();
+        in_file(
+            &lockfile_path,
+            Lockfile::from_results(&requests, &results)
+                .with_other_platforms(&previous)
+                .write(&lockfile_path),
+        )?;
+    }
+    Ok(())
+}
+
+async fn list(args: ListArgs) -> Result<()> {
+    let client = GitHubClient::new().with_api_key(args.token);
+    let (owner, repo, release) = GitHubClient::parse_repo(&args.repo)?;
+    let release = client.find_release(&owner, &repo, &release).await?;
+
+    if args.json {
+        // Serialising our own structs cannot fail
+        println!("{}", serde_json::to_string_pretty(&release).unwrap());
+        return Ok(());
+    }
+
+    let header = ["NAME", "SIZE", "CONTENT TYPE", "DIGEST", "URL"];
+    let rows: Vec<[String; 5]> = release
+        .assets
+        .iter()
+        .map(|a| {
+            let algorithm = a
+                .digest
+                .as_deref()
+                .and_then(|d| Hasher::parse_digest(d).ok())
+                .map_or("-".to_string(), |(algorithm, _)| algorithm.to_string());
+            [
+                a.name.clone(),
+                a.size.to_string(),
+                a.content_type.clone(),
+                algorithm,
+                a.browser_download_url.clone(),
+            ]
+        })
+        .collect();
+    println!("Release {}", release.tag_name);
+    print_table(header, &rows);
+    Ok(())
+}
+
+async fn install(args: InstallArgs) -> Result<()> {
+    let verification = &args.verification;
+    let installer = Installer::new()?;
+    let installer = match args.bin_dir {
+        Some(bin_dir) => installer.with_bin_dir(bin_dir),
+        None => installer,
     }
+    .with_binary(args.binary)
+    .with_name(args.name);
 
+    let asset_ref = asset_ref(&args.target, args.asset)?;
+    let (installed, download) = installer
+        .install(&downloader(verification)?, &asset_ref, verification.quiet)
+        .await?;
+    if verification.verbose {
+        print_download_result("downloaded and verified", &download);
+    }
+    if !verification.quiet {
+        println!(
+            "Installed {} {} to {}",
+            installed.name,
+            installed.tag,
+            installed.path.display()
+        );
+    }
     Ok(())
 }
+
+fn installed(args: InstalledArgs) -> Result<()> {
+    let installed = Installer::new()?.installed()?;
+
+    if args.json {
+        // Serialising our own structs cannot fail
+        println!("{}", serde_json::to_string_pretty(&installed).unwrap());
+        return Ok(());
+    }
+
+    let header = ["NAME", "VERSION", "REPOSITORY", "ASSET", "PATH"];
+    let rows: Vec<[String; 5]> = installed
+        .iter()
+        .map(|b| {
+            [
+                b.name.clone(),
+                b.tag.clone(),
+                format!("{}/{}", b.owner, b.repo),
+                b.asset.clone(),
+                b.path.display().to_string(),
+            ]
+        })
+        .collect();
+    print_table(header, &rows);
+    Ok(())
+}
+
+// Prints rows in aligned columns
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let header = header.map(str::to_string);
+    let widths: Vec<usize> = (0..N)
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(rows.iter())
+                .map(|r| r[i].len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+fn print_download_result(action: &str, result: &DownloadResult) {
+    print_result(
+        action,
+        Some(&result.path),
+        &result.clone().into(),
+        &result.extracted,
+        false,
+    );
+}
+
+// Prints the details of a verified file, written to path or streamed if there is none
+fn print_result(
+    action: &str,
+    path: Option<&Path>,
+    result: &StreamResult,
+    extracted: &[PathBuf],
+    to_stderr: bool,
+) {
+    let lines = [
+        format!("Successfully {} file:", action),
+        format!(
+            "  Path: {}",
+            path.map_or("-".into(), |p| p.display().to_string())
+        ),
+        format!("  Size: {} bytes", result.size),
+        format!("  Release: {}", result.tag),
+        format!("  Asset: {}", result.asset),
+        format!("  Algorithm: {}", result.algorithm),
+        format!("  Hash: {}", result.hash),
+    ]
+    .into_iter()
+    .chain(
+        result
+            .digests
+            .iter()
+            .skip(1)
+            .map(|(algorithm, hash)| format!("  {}: {}", algorithm, hash)),
+    )
+    .chain(
+        extracted
+            .iter()
+            .map(|path| format!("  Extracted: {}", path.display())),
+    )
+    .chain(result.signatures.iter().map(|s| match &s.comment {
+        Some(comment) => format!(
+            "  Signature: {} signed by {} ({})",
+            s.file, s.signer, comment
+        ),
+        None => format!("  Signature: {} signed by {}", s.file, s.signer),
+    }))
+    .chain(["  Checksum sources:".to_string()])
+    .chain(result.sources.iter().map(|r| {
+        let status = match &r.status {
+            SourceStatus::Matched => "matched".to_string(),
+            SourceStatus::Mismatched => "mismatched".to_string(),
+            SourceStatus::Unverified => "not verified".to_string(),
+            SourceStatus::Unavailable(reason) => format!("unavailable ({})", reason),
+            SourceStatus::NotConsulted => "not consulted".to_string(),
+        };
+        format!("    {}: {}", r.source, status)
+    }));
+    for line in lines {
+        if to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    client::{AssetRef, GitHubClient, ReleaseSpec},
+    downloader::DownloadRequest,
+    hasher::Hasher,
+    selector::AssetSelector,
+    sigstore::SigstoreIdentity,
+    Error, Result,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// List of files to download, read from a TOML file like
+//
+// [[download]]
+// url = "https://github.com/asfaload/asfald/releases/download/v0.8.0/asfald-x86_64-unknown-linux-musl"
+// output = "bin/asfald"
+//
+// [[download]]
+// repo = "jesseduffield/lazygit"
+// release = "^0.44"
+// asset = "lazygit_*_Linux_x86_64.tar.gz"
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub download: Vec<ManifestEntry>,
+}
+
+// A file to download, designated either by its URL or by its repository and asset selector
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    pub url: Option<String>,
+    // owner/repo
+    pub repo: Option<String>,
+    // Tag, latest or semver requirement, defaults to latest
+    pub release: Option<String>,
+    // Asset selector, defaults to auto
+    pub asset: Option<String>,
+    // Relative paths are relative to the current directory
+    pub output: Option<PathBuf>,
+    // Digests the file must have, as <algorithm>:<hex>
+    #[serde(default)]
+    pub expect: Vec<String>,
+    // Minisign public key the asset must be signed with
+    pub minisign_key: Option<String>,
+    // Regular expressions the identity and OIDC issuer of the certificate of the asset's
+    // Sigstore bundle must match, instead of the downloader's ones
+    pub certificate_identity_regexp: Option<String>,
+    pub certificate_oidc_issuer_regexp: Option<String>,
+}
+
+impl std::str::FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| Error::ManifestError(e.to_string()))
+    }
+}
+
+impl Manifest {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    // The download requests of all entries, failing on the first invalid entry
+    pub fn requests(&self) -> Result<Vec<DownloadRequest>> {
+        self.download
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                entry
+                    .request()
+                    .map_err(|e| Error::ManifestError(format!("entry {}: {}", i + 1, e)))
+            })
+            .collect()
+    }
+}
+
+impl ManifestEntry {
+    pub fn request(&self) -> Result<DownloadRequest> {
+        let asset = match (&self.url, &self.repo) {
+            (Some(url), None) => {
+                if self.release.is_some() || self.asset.is_some() {
+                    return Err(Error::ManifestError(
+                        "release and asset cannot be used with url".to_string(),
+                    ));
+                }
+                GitHubClient::parse_release_url(url)?
+            }
+            (None, Some(repo)) => {
+                let (owner, repo, release) = match &self.release {
+                    Some(release) if repo.contains('@') => {
+                        return Err(Error::ManifestError(format!(
+                            "release {} given twice for {}",
+                            release, repo
+                        )))
+                    }
+                    Some(release) => {
+                        let (owner, repo, _) = GitHubClient::parse_repo(repo)?;
+                        (owner, repo, release.parse::<ReleaseSpec>()?)
+                    }
+                    None => GitHubClient::parse_repo(repo)?,
+                };
+                AssetRef {
+                    owner,
+                    repo,
+                    release,
+                    asset: match &self.asset {
+                        Some(asset) => asset.parse()?,
+                        None => AssetSelector::auto(),
+                    },
+                }
+            }
+            _ => {
+                return Err(Error::ManifestError(
+                    "exactly one of url and repo must be given".to_string(),
+                ))
+            }
+        };
+        let identity = self.certificate_identity_regexp.as_deref();
+        let issuer = self.certificate_oidc_issuer_regexp.as_deref();
+        let sigstore_identity = match (identity, issuer) {
+            (Some(identity), Some(issuer)) => Some(SigstoreIdentity::new(identity, issuer)?),
+            (None, None) => None,
+            _ => {
+                return Err(Error::ManifestError(
+                    "certificate_identity_regexp and certificate_oidc_issuer_regexp must be \
+                     given together"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(DownloadRequest {
+            asset,
+            output: self.output.clone(),
+            expected: self
+                .expect
+                .iter()
+                .map(|digest| Hasher::parse_digest(digest))
+                .collect::<Result<_>>()?,
+            minisign_key: self.minisign_key.as_deref().map(str::parse).transpose()?,
+            sigstore_identity,
+        })
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    checksums::{self, ChecksumEntry},
+    Error, Result,
+};
+use reqwest::StatusCode;
+use std::str::FromStr;
+use url::Url;
+
+pub const DEFAULT_MIRROR_URL: &str = "https://gh.checksums.asfaload.com";
+
+// Name of the checksums file looked up when the release doesn't publish one
+const DEFAULT_CHECKSUMS_FILE: &str = "checksums.txt";
+
+// Client of the asfaload checksums mirror, which publishes copies of the checksums files
+// found in GitHub releases under the path of the original file, eg
+// https://gh.checksums.asfaload.com/github.com/asfaload/asfald/releases/download/v0.8.0/checksums.txt
+pub struct ChecksumsMirror {
+    client: reqwest::Client,
+    base_url: Url,
+}
+
+impl Default for ChecksumsMirror {
+    fn default() -> Self {
+        Self::new(Url::from_str(DEFAULT_MIRROR_URL).unwrap())
+    }
+}
+
+impl ChecksumsMirror {
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url,
+        }
+    }
+
+    // The URL of the mirrored copy of a checksums file, under the path of the base URL
+    fn checksums_file_url(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        checksums_file: &str,
+    ) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::MirrorError(format!("invalid mirror URL {}", self.base_url)))?
+            .pop_if_empty()
+            .extend(["github.com", owner, repo, "releases", "download"])
+            .extend(tag.split('/'))
+            .push(checksums_file);
+        Ok(url)
+    }
+
+    // Looks for the checksum of filename in the mirrored copies of the checksums files,
+    // trying them in order. The mirror having no copy of a file is not an error.
+    pub async fn get_checksum(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        checksums_files: &[&str],
+        filename: &str,
+    ) -> Result<ChecksumEntry> {
+        let checksums_files = if checksums_files.is_empty() {
+            &[DEFAULT_CHECKSUMS_FILE][..]
+        } else {
+            checksums_files
+        };
+
+        for checksums_file in checksums_files {
+            let url = self.checksums_file_url(owner, repo, tag, checksums_file)?;
+            let response = self
+                .client
+                .get(url)
+                .header("User-Agent", "Asfaload-downloader")
+                .send()
+                .await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+            let content = response.error_for_status()?.text().await?;
+
+            if let Some(entry) = checksums::lookup(checksums_file, &content, filename) {
+                return Ok(entry);
+            }
+        }
+
+        Err(Error::MirrorError(format!(
+            "no checksum found for {} on {}",
+            filename, self.base_url
+        )))
+    }
+}
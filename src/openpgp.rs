@@ -0,0 +1,476 @@
+use crate::{Error, Result};
+use pgp::{
+    composed::{Deserializable, DetachedSignature, SignedPublicKey, SignedPublicSubKey},
+    crypto::hash::HashAlgorithm,
+    packet::{KeyFlags, PublicKey, PublicSubkey, Signature, SignatureType, SubpacketData},
+    types::{Fingerprint, KeyDetails, KeyId, Tag},
+};
+use std::io::{PipeWriter, Read, Write};
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Extensions of OpenPGP detached signatures, armored (gpg --armor --detach-sign) or binary.
+// The .sig extension is shared with SSH signatures, they are told apart by their content.
+pub const OPENPGP_SIGNATURE_EXTENSIONS: [&str; 3] = [".asc", ".sig", ".gpg"];
+
+// The public keys trusted to sign files, read from a keyring file as exported by
+// gpg --export [--armor]. Keys are trusted as is, third party certifications are ignored.
+// Signing subkeys are only used if they signed their primary key back.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    certificates: Vec<Certificate>,
+}
+
+// A primary key with its subkeys, as far as signing files is concerned
+#[derive(Debug, Clone)]
+struct Certificate {
+    // The primary user ID, eg Asfaload Release <release@asfaload.com>
+    user_id: Option<String>,
+    // The primary key first, followed by the subkeys bound to it
+    keys: Vec<SigningKey>,
+}
+
+#[derive(Debug, Clone)]
+struct SigningKey {
+    key: Key,
+    fingerprint: Fingerprint,
+    key_id: KeyId,
+    // As seconds since the epoch
+    created: u64,
+    // Set by the latest self-signature, as seconds since the epoch
+    expiration: Option<u64>,
+    flags: Option<KeyFlags>,
+    revoked: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Key {
+    Primary(PublicKey),
+    Subkey(PublicSubkey),
+}
+
+// A detached signature, as generated by gpg --detach-sign, which may hold the signatures
+// of several signers
+#[derive(Debug, Clone)]
+pub struct PgpSignature {
+    signatures: Vec<Signature>,
+}
+
+impl Keyring {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let failed = |e: pgp::errors::Error| Error::KeyringError(e.to_string());
+        let (keys, _) = SignedPublicKey::from_reader_many(data).map_err(failed)?;
+        let certificates = keys
+            .map(|key| key.map(|key| Certificate::new(&key)))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(failed)?;
+        if certificates.is_empty() {
+            return Err(Error::KeyringError(
+                "no supported public key found".to_string(),
+            ));
+        }
+        Ok(Self { certificates })
+    }
+
+    // Verifies the signature of message, returning the signer
+    pub fn verify(&self, signature: &PgpSignature, message: &[u8]) -> Result<String> {
+        let mut verification = self.verification(signature)?;
+        verification.write_all(message)?;
+        verification.finish()
+    }
+
+    // Starts the verification of a signed file, whose content must then be written to the
+    // returned verification. The signatures by keys of the keyring are each verified in a
+    // thread reading the content from a pipe, as the OpenPGP library only verifies data it
+    // reads itself.
+    pub fn verification<'a>(&'a self, signature: &'a PgpSignature) -> Result<PgpVerification<'a>> {
+        let mut pending = vec![];
+        let mut unknown = vec![];
+        for sig in &signature.signatures {
+            let Some((certificate, key)) = self.find(sig) else {
+                unknown.push(issuer_description(sig));
+                continue;
+            };
+            let (reader, writer) = std::io::pipe()?;
+            let (verifying_key, sig_clone) = (key.key.clone(), sig.clone());
+            let thread = std::thread::spawn(move || verifying_key.verify(&sig_clone, reader));
+            pending.push(PendingVerification {
+                certificate,
+                key,
+                signature: sig,
+                writer: Some(writer),
+                thread,
+            });
+        }
+        Ok(PgpVerification { pending, unknown })
+    }
+
+    // The certificate and key which made the signature, if it is in the keyring
+    fn find(&self, sig: &Signature) -> Option<(&Certificate, &SigningKey)> {
+        let fingerprints = sig.issuer_fingerprint();
+        let key_ids = sig.issuer_key_id();
+        self.certificates.iter().find_map(|certificate| {
+            certificate
+                .keys
+                .iter()
+                .find(|key| {
+                    if fingerprints.is_empty() {
+                        key_ids.contains(&&key.key_id)
+                    } else {
+                        fingerprints.contains(&&key.fingerprint)
+                    }
+                })
+                .map(|key| (certificate, key))
+        })
+    }
+}
+
+impl Certificate {
+    // Self-signatures which are not valid are ignored, and so are subkeys without a valid
+    // binding signature
+    fn new(key: &SignedPublicKey) -> Self {
+        let primary = &key.primary_key;
+        let user_ids: Vec<(String, &Signature)> = key
+            .details
+            .users
+            .iter()
+            .filter_map(|user| {
+                let sig = latest(user.signatures.iter().filter(|sig| {
+                    sig.verify_certification(primary, Tag::UserId, &user.id)
+                        .is_ok()
+                }))?;
+                Some((String::from_utf8_lossy(user.id.id()).into_owned(), sig))
+            })
+            .collect();
+        let direct = key
+            .details
+            .direct_signatures
+            .iter()
+            .filter(|sig| sig.verify_key(primary).is_ok());
+        let revoked = key
+            .details
+            .revocation_signatures
+            .iter()
+            .any(|sig| sig.verify_key(primary).is_ok());
+
+        // The primary key's properties come from its latest self-signature
+        let self_signature = latest(user_ids.iter().map(|(_, sig)| *sig).chain(direct));
+        let user_id = user_ids
+            .iter()
+            .find(|(_, sig)| sig.is_primary())
+            .or(user_ids.first())
+            .map(|(user_id, _)| user_id.clone())
+            .or_else(|| {
+                let user = key.details.users.first()?;
+                Some(String::from_utf8_lossy(user.id.id()).into_owned())
+            });
+
+        let mut keys = vec![SigningKey::new(
+            Key::Primary(primary.clone()),
+            self_signature,
+            revoked,
+        )];
+        for subkey in &key.public_subkeys {
+            let bound = |sig: &&Signature| sig.verify_subkey_binding(primary, &subkey.key).is_ok();
+            let revoked = subkey
+                .signatures
+                .iter()
+                .filter(|sig| sig.typ() == Some(SignatureType::SubkeyRevocation))
+                .any(|sig| bound(&sig));
+            let binding = latest(
+                subkey
+                    .signatures
+                    .iter()
+                    .filter(|sig| sig.typ() == Some(SignatureType::SubkeyBinding))
+                    .filter(bound)
+                    .filter(|sig| is_back_signed(sig, subkey, primary)),
+            );
+            if let Some(binding) = binding {
+                let key = Key::Subkey(subkey.key.clone());
+                keys.push(SigningKey::new(key, Some(binding), revoked));
+            }
+        }
+
+        Self { user_id, keys }
+    }
+
+    // The user ID and fingerprint of the certificate
+    fn signer(&self) -> String {
+        let fingerprint = hex::encode_upper(self.keys[0].fingerprint.as_bytes());
+        match &self.user_id {
+            Some(user_id) => format!("{} ({})", user_id, fingerprint),
+            None => fingerprint,
+        }
+    }
+
+    // Checks that key, of this certificate, could make sig, which has been verified
+    fn check_validity(&self, key: &SigningKey, sig: &Signature, now: u64) -> Result<()> {
+        let primary = &self.keys[0];
+        if primary.revoked || key.revoked {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "key {} has been revoked",
+                self.signer()
+            )));
+        }
+        for key in [primary, key] {
+            if let Some(expiration) = key.expiration.filter(|e| *e <= now) {
+                return Err(Error::KeyExpired(format!(
+                    "key {} expired on {}",
+                    self.signer(),
+                    format_time(expiration)
+                )));
+            }
+        }
+        if key.flags.as_ref().is_some_and(|flags| !flags.sign()) {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "key {} of {} is not allowed to sign data",
+                hex::encode_upper(key.fingerprint.as_bytes()),
+                self.signer()
+            )));
+        }
+        if let Some(
+            algorithm @ (HashAlgorithm::Md5 | HashAlgorithm::Sha1 | HashAlgorithm::Ripemd160),
+        ) = sig.hash_alg()
+        {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "{} signatures are not accepted",
+                algorithm
+            )));
+        }
+
+        // A signature can only be made by an existing key, and not later than now
+        let created = sig
+            .created()
+            .map(|created| created.as_secs() as u64)
+            .ok_or_else(|| {
+                Error::SignatureVerificationFailed(format!(
+                    "the signature by {} has no creation time",
+                    self.signer()
+                ))
+            })?;
+        if created < key.created {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "the signature by {} was made on {}, before its key was created",
+                self.signer(),
+                format_time(created)
+            )));
+        }
+        if created > now {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "the signature by {} was made in the future, on {}",
+                self.signer(),
+                format_time(created)
+            )));
+        }
+        if let Some(expiration) = sig
+            .signature_expiration_time()
+            .map(|e| e.as_secs() as u64)
+            .filter(|e| *e != 0)
+        {
+            let expiration = created + expiration;
+            if expiration <= now {
+                return Err(Error::SignatureVerificationFailed(format!(
+                    "the signature by {} expired on {}",
+                    self.signer(),
+                    format_time(expiration)
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SigningKey {
+    fn new(key: Key, self_signature: Option<&Signature>, revoked: bool) -> Self {
+        let details: &dyn KeyDetails = match &key {
+            Key::Primary(key) => key,
+            Key::Subkey(key) => key,
+        };
+        let created = details.created_at().as_secs() as u64;
+        let (fingerprint, key_id) = (details.fingerprint(), details.legacy_key_id());
+        // An expiration of 0 means the key doesn't expire
+        let expiration = self_signature
+            .and_then(|sig| sig.key_expiration_time())
+            .map(|e| e.as_secs() as u64)
+            .filter(|e| *e != 0)
+            .map(|e| created + e);
+        Self {
+            key,
+            fingerprint,
+            key_id,
+            created,
+            expiration,
+            flags: self_signature.and_then(key_flags),
+            revoked,
+        }
+    }
+}
+
+impl Key {
+    // Checks that sig is a signature by this key of the data read
+    fn verify(&self, sig: &Signature, data: impl Read) -> pgp::errors::Result<()> {
+        match self {
+            Key::Primary(key) => sig.verify(key, data),
+            Key::Subkey(key) => sig.verify(key, data),
+        }
+    }
+}
+
+impl std::str::FromStr for PgpSignature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_bytes(s.as_bytes())
+    }
+}
+
+impl PgpSignature {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let invalid = |e: pgp::errors::Error| Error::InvalidSignature(e.to_string());
+        let (signatures, _) = DetachedSignature::from_reader_many(data).map_err(invalid)?;
+        let signatures = signatures
+            .map(|signature| signature.map(|s| s.signature))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        if let Some(sig) = signatures.iter().find(|s| {
+            !matches!(
+                s.typ(),
+                Some(SignatureType::Binary) | Some(SignatureType::Text)
+            )
+        }) {
+            return Err(Error::InvalidSignature(format!(
+                "not a signature of a file, but of type {:?}",
+                sig.typ()
+            )));
+        }
+        if signatures.is_empty() {
+            return Err(Error::InvalidSignature(
+                "no OpenPGP signature found".to_string(),
+            ));
+        }
+        Ok(Self { signatures })
+    }
+
+    // Whether data looks like an OpenPGP signature, armored or starting with a signature
+    // packet, rather than eg an SSH signature
+    pub fn is_openpgp(data: &[u8]) -> bool {
+        data.trim_ascii_start()
+            .starts_with(b"-----BEGIN PGP SIGNATURE-----")
+            // The new and old format headers of signature packets
+            || matches!(data.first(), Some(0xc2 | 0x88..=0x8b))
+    }
+}
+
+// The verification of an OpenPGP signature, fed with the content of the signed file
+pub struct PgpVerification<'a> {
+    pending: Vec<PendingVerification<'a>>,
+    // The issuers of the signatures by keys which are not in the keyring
+    unknown: Vec<String>,
+}
+
+// A signature by a key of the keyring, verified in a thread reading the content from a pipe
+struct PendingVerification<'a> {
+    certificate: &'a Certificate,
+    key: &'a SigningKey,
+    signature: &'a Signature,
+    // Closed once the content has been written, or when the verification stopped reading
+    writer: Option<PipeWriter>,
+    thread: JoinHandle<pgp::errors::Result<()>>,
+}
+
+impl Write for PgpVerification<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for pending in &mut self.pending {
+            // The verification only stops reading when it fails, which finish reports
+            if let Some(writer) = &mut pending.writer {
+                if writer.write_all(buf).is_err() {
+                    pending.writer = None;
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PgpVerification<'_> {
+    // Checks the signatures of the content written, returning the signer. Signatures by
+    // keys which are not in the keyring are ignored as long as one signature is from a
+    // key of the keyring, but all those from the keyring must be valid.
+    pub fn finish(self) -> Result<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut signer = None;
+        for mut pending in self.pending {
+            let certificate = pending.certificate;
+            // Closing the pipe ends the content read by the verification
+            pending.writer = None;
+            pending
+                .thread
+                .join()
+                .map_err(|_| "the verification failed".to_string())
+                .and_then(|result| result.map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    Error::SignatureVerificationFailed(format!("{} by {}", e, certificate.signer()))
+                })?;
+            certificate.check_validity(pending.key, pending.signature, now)?;
+            signer.get_or_insert_with(|| certificate.signer());
+        }
+        signer.ok_or_else(|| {
+            Error::UnknownSigner(format!("{} is not in the keyring", self.unknown.join(", ")))
+        })
+    }
+}
+
+// Keeps the most recent of the signatures
+fn latest<'a>(sigs: impl Iterator<Item = &'a Signature>) -> Option<&'a Signature> {
+    sigs.max_by_key(|sig| sig.created())
+}
+
+// The key flags of a self-signature, if it has some
+fn key_flags(sig: &Signature) -> Option<KeyFlags> {
+    sig.config()?
+        .hashed_subpackets()
+        .find_map(|subpacket| match &subpacket.data {
+            SubpacketData::KeyFlags(flags) => Some(flags.clone()),
+            _ => None,
+        })
+}
+
+// Whether the subkey bound by binding signed the primary key back if it may sign, with a
+// signature embedded in the binding. Otherwise anyone could bind the signing subkey of
+// someone else to their own certificate, and claim their signatures.
+fn is_back_signed(binding: &Signature, subkey: &SignedPublicSubKey, primary: &PublicKey) -> bool {
+    if key_flags(binding).is_some_and(|flags| !flags.sign()) {
+        return true;
+    }
+    binding.embedded_signature().is_some_and(|back| {
+        back.verify_primary_key_binding(&subkey.key, primary)
+            .is_ok()
+    })
+}
+
+fn issuer_description(sig: &Signature) -> String {
+    match (
+        sig.issuer_fingerprint().first(),
+        sig.issuer_key_id().first(),
+    ) {
+        (Some(fingerprint), _) => format!("key {}", hex::encode_upper(fingerprint.as_bytes())),
+        (None, Some(key_id)) => format!("key ID {}", hex::encode_upper(key_id)),
+        (None, None) => "a signature without issuer".to_string(),
+    }
+}
+
+fn format_time(time: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(time))
+}
@@ -0,0 +1,247 @@
+use crate::{hasher::HashAlgorithm, Error, Result};
+
+// Places where the expected checksum of a file can be found, in the order they are consulted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumSource {
+    // A digest given by the user or recorded in a lockfile
+    Pinned,
+    // The digest computed by GitHub for the release asset
+    AssetDigest,
+    // A checksums file published in the release, eg checksums.txt
+    ReleaseChecksums,
+    // The copy of the release's checksums file on the asfaload checksums mirror
+    Mirror,
+}
+
+impl std::str::FromStr for ChecksumSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "asset-digest" | "digest" => Ok(ChecksumSource::AssetDigest),
+            "release-checksums" | "checksums" => Ok(ChecksumSource::ReleaseChecksums),
+            "mirror" => Ok(ChecksumSource::Mirror),
+            "pinned" => Ok(ChecksumSource::Pinned),
+            _ => Err(Error::InvalidPolicy(format!(
+                "unknown checksum source {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksumSource::AssetDigest => write!(f, "asset-digest"),
+            ChecksumSource::ReleaseChecksums => write!(f, "release-checksums"),
+            ChecksumSource::Mirror => write!(f, "mirror"),
+            ChecksumSource::Pinned => write!(f, "pinned"),
+        }
+    }
+}
+
+// How checksums from different sources are combined to accept a download.
+// In all modes but Any, sources that have a checksum for the file must agree. In all modes,
+// the mirror must have a checksum for the file if it was requested.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum VerificationPolicy {
+    // The first source having a checksum for the file is used, the others are not consulted
+    #[default]
+    Any,
+    // All sources are consulted, at least one must have a checksum for the file
+    AllAvailable,
+    // All sources are consulted, at least n must have a checksum for the file
+    AtLeast(usize),
+    // All sources are consulted, the given source must have a checksum for the file
+    Require(ChecksumSource),
+}
+
+impl std::str::FromStr for VerificationPolicy {
+    type Err = Error;
+
+    // Accepts any, all, at-least:<n> and require:<source>
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "any" => Ok(VerificationPolicy::Any),
+            None if s == "all" => Ok(VerificationPolicy::AllAvailable),
+            Some(("at-least", n)) => n
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .map(VerificationPolicy::AtLeast)
+                .ok_or_else(|| Error::InvalidPolicy(format!("invalid number of sources {}", n))),
+            Some(("require", source)) => Ok(VerificationPolicy::Require(source.parse()?)),
+            _ => Err(Error::InvalidPolicy(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for VerificationPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationPolicy::Any => write!(f, "any"),
+            VerificationPolicy::AllAvailable => write!(f, "all"),
+            VerificationPolicy::AtLeast(n) => write!(f, "at-least:{}", n),
+            VerificationPolicy::Require(source) => write!(f, "require:{}", source),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceStatus {
+    // The downloaded file has the checksum found in the source
+    Matched,
+    // The downloaded file doesn't have the checksum found in the source
+    Mismatched,
+    // A checksum was found in the source, but the downloaded file was not checked against it
+    Unverified,
+    // No checksum could be found in the source, with the reason
+    Unavailable(String),
+    // The source was not consulted as the policy didn't require it
+    NotConsulted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceReport {
+    pub source: ChecksumSource,
+    // The checksum found in the source, if any
+    pub expected: Option<(HashAlgorithm, String)>,
+    pub status: SourceStatus,
+}
+
+impl SourceReport {
+    pub fn is_available(&self) -> bool {
+        self.expected.is_some()
+    }
+}
+
+impl VerificationPolicy {
+    // Returns true if consulting further sources is useless once one is available
+    pub(crate) fn stops_at_first_available(&self) -> bool {
+        matches!(self, VerificationPolicy::Any)
+    }
+
+    // Checks the checksums collected before downloading the file, so that we don't download it
+    // if the policy cannot be satisfied.
+    pub(crate) fn check_sources(&self, filename: &str, reports: &[SourceReport]) -> Result<()> {
+        let available: Vec<&SourceReport> = reports.iter().filter(|r| r.is_available()).collect();
+
+        if available.is_empty() {
+            let reasons: Vec<String> = reports
+                .iter()
+                .filter_map(|r| match &r.status {
+                    SourceStatus::Unavailable(reason) => Some(format!("{}: {}", r.source, reason)),
+                    _ => None,
+                })
+                .collect();
+            return Err(Error::ChecksumNotFound(format!(
+                "{} ({})",
+                filename,
+                reasons.join("; ")
+            )));
+        }
+
+        // The mirror is only consulted when explicitly requested, to cross-check the other
+        // sources, whatever the policy
+        if let Some(mirror) = reports
+            .iter()
+            .find(|r| r.source == ChecksumSource::Mirror && !r.is_available())
+        {
+            return Err(Error::PolicyNotSatisfied(format!(
+                "no checksum found on the requested mirror ({})",
+                match &mirror.status {
+                    SourceStatus::Unavailable(reason) => reason.as_str(),
+                    _ => "not consulted",
+                }
+            )));
+        }
+
+        match self {
+            VerificationPolicy::Any | VerificationPolicy::AllAvailable => {}
+            VerificationPolicy::AtLeast(n) => {
+                if available.len() < *n {
+                    return Err(Error::PolicyNotSatisfied(format!(
+                        "{} source(s) required, {} available",
+                        n,
+                        available.len()
+                    )));
+                }
+            }
+            VerificationPolicy::Require(source) => {
+                if !available.iter().any(|r| r.source == *source) {
+                    return Err(Error::PolicyNotSatisfied(format!(
+                        "no checksum found in required source {}",
+                        source
+                    )));
+                }
+            }
+        }
+
+        // Sources using the same algorithm must agree. Pinned digests are compared with the
+        // downloaded file instead, so that a disagreement is reported as a verification
+        // failure of the source which doesn't match the file.
+        let compared: Vec<&&SourceReport> = available
+            .iter()
+            .filter(|r| r.source != ChecksumSource::Pinned)
+            .collect();
+        for (i, a) in compared.iter().enumerate() {
+            for b in &compared[i + 1..] {
+                if let (Some((alg_a, hash_a)), Some((alg_b, hash_b))) = (&a.expected, &b.expected) {
+                    if alg_a == alg_b && hash_a != hash_b {
+                        return Err(Error::ChecksumsDisagree(format!(
+                            "{} has {}:{}, {} has {}:{}",
+                            a.source, alg_a, hash_a, b.source, alg_b, hash_b
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Compares the computed digests with the checksum of each available source, updating
+    // their status. Fails if any of them is different.
+    pub(crate) fn verify(
+        &self,
+        reports: &mut [SourceReport],
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<()> {
+        for report in reports.iter_mut() {
+            let Some((expected_algorithm, expected_hash)) = &report.expected else {
+                continue;
+            };
+            let Some((_, actual_hash)) = digests.iter().find(|(a, _)| a == expected_algorithm)
+            else {
+                continue;
+            };
+            if expected_hash != actual_hash {
+                report.status = SourceStatus::Mismatched;
+                return Err(Error::HashVerificationFailed {
+                    expected: expected_hash.clone(),
+                    actual: actual_hash.clone(),
+                });
+            }
+            report.status = SourceStatus::Matched;
+        }
+
+        let matched = |r: &&SourceReport| r.status == SourceStatus::Matched;
+        let satisfied = match self {
+            VerificationPolicy::Any | VerificationPolicy::AllAvailable => {
+                reports.iter().any(|r| matched(&r))
+            }
+            VerificationPolicy::AtLeast(n) => reports.iter().filter(matched).count() >= *n,
+            VerificationPolicy::Require(source) => {
+                reports.iter().any(|r| r.source == *source && matched(&r))
+            }
+        };
+        if !satisfied {
+            return Err(Error::PolicyNotSatisfied(format!(
+                "not enough checksums verified for policy {}",
+                self
+            )));
+        }
+        Ok(())
+    }
+}
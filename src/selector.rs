@@ -0,0 +1,238 @@
+use crate::{checksums, client::GitHubAsset, Error, Result};
+
+// Names used in asset names for each OS, as reported by std::env::consts::OS
+const OS_ALIASES: [(&str, &[&str]); 4] = [
+    ("linux", &["linux"]),
+    ("macos", &["macos", "darwin", "apple", "osx"]),
+    ("windows", &["windows", "win64", "win32", "win"]),
+    ("freebsd", &["freebsd"]),
+];
+
+// Names used in asset names for each architecture, as reported by std::env::consts::ARCH
+const ARCH_ALIASES: [(&str, &[&str]); 2] = [
+    ("x86_64", &["x86_64", "x86-64", "amd64", "x64"]),
+    ("aarch64", &["aarch64", "arm64"]),
+];
+
+// Assets built for all architectures, eg macOS universal binaries
+const ANY_ARCH: [&str; 2] = ["universal", "all"];
+
+const LIBCS: [&str; 2] = ["musl", "gnu"];
+
+// Extensions of files which are not the distributed software itself
+const IGNORED_EXTENSIONS: [&str; 12] = [
+    ".sig",
+    ".asc",
+    ".minisig",
+    ".pem",
+    ".crt",
+    ".sbom",
+    ".json",
+    ".intoto.jsonl",
+    ".deb",
+    ".rpm",
+    ".apk",
+    ".msi",
+];
+
+// The platform an asset is selected for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    pub os: String,
+    pub arch: String,
+    // musl or gnu on Linux, None elsewhere
+    pub libc: Option<String>,
+}
+
+impl Platform {
+    // The platform asfald is running on
+    pub fn host() -> Self {
+        let libc = if std::env::consts::OS != "linux" {
+            None
+        } else if cfg!(target_env = "musl") {
+            Some("musl".to_string())
+        } else {
+            Some("gnu".to_string())
+        };
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            libc,
+        }
+    }
+
+    // Scores how well an asset fits the platform, None if it is not built for it.
+    // An asset must name both the OS and the architecture of the platform. On Linux,
+    // builds for the platform's libc are preferred, followed by builds not naming their
+    // libc, and musl builds which are usually static and run on glibc systems too.
+    fn score(&self, name: &str) -> Option<u32> {
+        let name = name.to_lowercase();
+        if IGNORED_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+            || checksums::is_checksums_file(&name)
+            || checksums::has_checksum_extension(&name)
+        {
+            return None;
+        }
+
+        let mentions = |words: &[&str]| words.iter().any(|w| contains_word(&name, w));
+
+        if !mentions(&aliases(&OS_ALIASES, &self.os)) {
+            return None;
+        }
+        let arch_score = if mentions(&aliases(&ARCH_ALIASES, &self.arch)) {
+            2
+        } else if mentions(&ANY_ARCH) {
+            1
+        } else {
+            return None;
+        };
+
+        let libc = LIBCS.iter().find(|l| contains_word(&name, l));
+        let libc_score = match (self.libc.as_deref(), libc) {
+            (None, _) | (Some(_), None) => 1,
+            (Some(host), Some(libc)) if host == *libc => 2,
+            (Some(_), Some(&"musl")) => 0,
+            // glibc builds don't run on musl systems
+            (Some(_), Some(_)) => return None,
+        };
+
+        Some(arch_score * 10 + libc_score)
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.os, self.arch)?;
+        if let Some(libc) = &self.libc {
+            write!(f, "/{}", libc)?;
+        }
+        Ok(())
+    }
+}
+
+// Selects the asset to download among the assets of a release
+#[derive(Debug, Clone)]
+pub enum AssetSelector {
+    // The asset with exactly this name
+    Name(String),
+    Glob(glob::Pattern),
+    // The regex is searched in the names, use ^ and $ to match whole names
+    Regex(regex::Regex),
+    // The asset built for the platform
+    Platform(Platform),
+}
+
+impl AssetSelector {
+    // Selects the asset built for the platform asfald is running on
+    pub fn auto() -> Self {
+        AssetSelector::Platform(Platform::host())
+    }
+
+    // Returns the only asset matching the selector. Fails if none or several match.
+    pub fn select<'a>(&self, assets: &'a [GitHubAsset]) -> Result<&'a GitHubAsset> {
+        let candidates: Vec<&GitHubAsset> = match self {
+            AssetSelector::Name(name) => assets.iter().filter(|a| &a.name == name).collect(),
+            AssetSelector::Glob(pattern) => {
+                assets.iter().filter(|a| pattern.matches(&a.name)).collect()
+            }
+            AssetSelector::Regex(regex) => {
+                assets.iter().filter(|a| regex.is_match(&a.name)).collect()
+            }
+            AssetSelector::Platform(platform) => {
+                let scored: Vec<(u32, &GitHubAsset)> = assets
+                    .iter()
+                    .filter_map(|a| platform.score(&a.name).map(|score| (score, a)))
+                    .collect();
+                let best = scored.iter().map(|(score, _)| *score).max();
+                scored
+                    .into_iter()
+                    .filter(|(score, _)| Some(*score) == best)
+                    .map(|(_, a)| a)
+                    .collect()
+            }
+        };
+
+        match candidates.as_slice() {
+            [asset] => Ok(asset),
+            [] => Err(Error::AssetNotFound(self.to_string())),
+            _ => Err(Error::AmbiguousAsset {
+                selector: self.to_string(),
+                candidates: candidates.iter().map(|a| a.name.clone()).collect(),
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for AssetSelector {
+    type Err = Error;
+
+    // Accepts auto, glob:<pattern>, regex:<regex> and asset names. Names containing
+    // glob special characters (*, ?, [) are read as glob patterns.
+    fn from_str(s: &str) -> Result<Self> {
+        let glob = |pattern: &str| {
+            glob::Pattern::new(pattern)
+                .map(AssetSelector::Glob)
+                .map_err(|e| Error::InvalidAssetSelector(format!("{}: {}", s, e)))
+        };
+        if s == "auto" {
+            Ok(AssetSelector::auto())
+        } else if let Some(pattern) = s.strip_prefix("glob:") {
+            glob(pattern)
+        } else if let Some(regex) = s.strip_prefix("regex:") {
+            regex::Regex::new(regex)
+                .map(AssetSelector::Regex)
+                .map_err(|e| Error::InvalidAssetSelector(format!("{}: {}", s, e)))
+        } else if s.contains(['*', '?', '[']) {
+            glob(s)
+        } else if s.is_empty() {
+            Err(Error::InvalidAssetSelector(s.to_string()))
+        } else {
+            Ok(AssetSelector::Name(s.to_string()))
+        }
+    }
+}
+
+impl std::fmt::Display for AssetSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetSelector::Name(name) => write!(f, "{}", name),
+            AssetSelector::Glob(pattern) => write!(f, "glob:{}", pattern),
+            AssetSelector::Regex(regex) => write!(f, "regex:{}", regex),
+            AssetSelector::Platform(platform) => write!(f, "auto ({})", platform),
+        }
+    }
+}
+
+// Regex doesn't implement PartialEq, regexes are compared on their source
+impl PartialEq for AssetSelector {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AssetSelector::Name(a), AssetSelector::Name(b)) => a == b,
+            (AssetSelector::Glob(a), AssetSelector::Glob(b)) => a == b,
+            (AssetSelector::Regex(a), AssetSelector::Regex(b)) => a.as_str() == b.as_str(),
+            (AssetSelector::Platform(a), AssetSelector::Platform(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AssetSelector {}
+
+// The names used for key in asset names, key itself if it has no known alias
+fn aliases<'a>(table: &[(&str, &[&'a str])], key: &'a str) -> Vec<&'a str> {
+    table
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map_or(vec![key], |(_, names)| names.to_vec())
+}
+
+// Returns true if word appears in name, delimited by non alphanumeric characters,
+// so that eg win doesn't match darwin
+fn contains_word(name: &str, word: &str) -> bool {
+    name.match_indices(word).any(|(i, _)| {
+        let before = name[..i].chars().next_back();
+        let after = name[i + word.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric())
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    })
+}
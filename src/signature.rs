@@ -0,0 +1,389 @@
+use crate::{hasher::HashAlgorithm, Error, Result};
+use serde::Serialize;
+use ssh_key::{HashAlg, PublicKey, SshSig};
+use std::io::Write;
+use std::path::Path;
+
+// Namespace used by ssh-keygen -Y sign for files, unless another one is given with -n
+pub const DEFAULT_SIGNATURE_NAMESPACE: &str = "file";
+
+// Extension of the signatures generated by ssh-keygen -Y sign
+pub const SSH_SIGNATURE_EXTENSION: &str = ".sig";
+
+// Extension of the signatures generated by minisign
+pub const MINISIGN_SIGNATURE_EXTENSION: &str = ".minisig";
+
+// The keys trusted to sign files, read from a file in the format used by ssh-keygen -Y verify:
+//
+// # principals [options] keytype key [comment]
+// release@asfaload.com namespaces="file" ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAI...
+//
+// Certificate authorities and validity periods are not supported.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AllowedSigners {
+    pub signers: Vec<AllowedSigner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedSigner {
+    // As written in the file, comma separated
+    pub principals: String,
+    // Patterns of the namespaces the key may sign for, any namespace if None
+    pub namespaces: Option<Vec<String>>,
+    pub key: PublicKey,
+}
+
+// A signature whose signer was found in the allowed signers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifiedSignature {
+    // Name of the signed file, the asset or a checksums file
+    pub file: String,
+    // Name of the signature file
+    pub signature: String,
+    // Principals of the signer for SSH signatures, the public key for minisign
+    pub signer: String,
+    // The trusted comment of minisign signatures, eg timestamp:1555779966\tfile:asfald
+    pub comment: Option<String>,
+}
+
+// A signature generated by ssh-keygen -Y sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshSignature(SshSig);
+
+impl std::str::FromStr for SshSignature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        SshSig::from_pem(s.trim())
+            .map(SshSignature)
+            .map_err(|e| Error::InvalidSignature(e.to_string()))
+    }
+}
+
+impl SshSignature {
+    // The algorithm the signed data was hashed with before being signed
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        match self.0.hash_alg() {
+            HashAlg::Sha256 => HashAlgorithm::Sha256,
+            // HashAlg is non exhaustive, but only has sha256 and sha512
+            _ => HashAlgorithm::Sha512,
+        }
+    }
+}
+
+impl std::str::FromStr for AllowedSigners {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let signers = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| {
+                AllowedSigner::parse(line).map_err(|reason| {
+                    Error::AllowedSignersError(format!("line {}: {}", i + 1, reason))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { signers })
+    }
+}
+
+impl AllowedSigners {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    // Verifies the signature of message for namespace, returning the principals of the signer
+    pub fn verify(
+        &self,
+        namespace: &str,
+        signature: &SshSignature,
+        message: &[u8],
+    ) -> Result<String> {
+        let hash = match signature.0.hash_alg() {
+            HashAlg::Sha256 => HashAlg::Sha256.digest(message),
+            _ => HashAlg::Sha512.digest(message),
+        };
+        self.verify_hash(namespace, signature, &hash)
+    }
+
+    // Verifies the signature of a file from its digest, computed with the signature's
+    // hash algorithm, so that large files don't have to be held in memory.
+    pub fn verify_digest(
+        &self,
+        namespace: &str,
+        signature: &SshSignature,
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<String> {
+        let algorithm = signature.hash_algorithm();
+        let (_, hash) = digests
+            .iter()
+            .find(|(a, _)| *a == algorithm)
+            .ok_or_else(|| {
+                Error::SignatureVerificationFailed(format!("no {} digest computed", algorithm))
+            })?;
+        let hash = hex::decode(hash).map_err(|e| Error::HashError(e.to_string()))?;
+        self.verify_hash(namespace, signature, &hash)
+    }
+
+    fn verify_hash(
+        &self,
+        namespace: &str,
+        signature: &SshSignature,
+        hash: &[u8],
+    ) -> Result<String> {
+        let sig = &signature.0;
+        // Signatures for another namespace, eg git commits, must not be accepted for files
+        if sig.namespace() != namespace {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "signed for namespace {}, expected {}",
+                sig.namespace(),
+                namespace
+            )));
+        }
+        let signer = self
+            .signers
+            .iter()
+            .find(|s| s.key.key_data() == sig.public_key() && s.allows_namespace(namespace))
+            .ok_or_else(|| {
+                Error::SignatureVerificationFailed(format!(
+                    "key {} is not an allowed signer",
+                    PublicKey::from(sig.public_key().clone()).fingerprint(HashAlg::Sha256)
+                ))
+            })?;
+
+        ::signature::Verifier::verify(
+            sig.public_key(),
+            &signed_data(namespace, sig, hash),
+            sig.signature(),
+        )
+        .map_err(|e| Error::SignatureVerificationFailed(e.to_string()))?;
+        Ok(signer.principals.clone())
+    }
+}
+
+impl AllowedSigner {
+    // Errors are reported with the line number by the caller
+    fn parse(line: &str) -> std::result::Result<Self, String> {
+        let mut fields = Fields(line.trim());
+        let principals = fields.next().ok_or("missing principals")?;
+
+        // Options are optional, the key type follows the principals if there are none
+        let mut namespaces = None;
+        let mut key_type = fields.next().ok_or("missing key")?;
+        if !is_key_type(&key_type) {
+            for option in split_options(&key_type) {
+                match option.split_once('=') {
+                    Some(("namespaces", value)) => {
+                        namespaces = Some(
+                            value
+                                .trim_matches('"')
+                                .split(',')
+                                .map(str::to_string)
+                                .collect(),
+                        )
+                    }
+                    _ => return Err(format!("unsupported option {}", option)),
+                }
+            }
+            key_type = fields.next().ok_or("missing key")?;
+        }
+        let key_data = fields.next().ok_or("missing key")?;
+        let key = PublicKey::from_openssh(&format!("{} {}", key_type, key_data))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            principals: principals.trim_matches('"').to_string(),
+            namespaces,
+            key,
+        })
+    }
+
+    fn allows_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.as_ref().is_none_or(|patterns| {
+            patterns
+                .iter()
+                .any(|p| glob::Pattern::new(p).is_ok_and(|pattern| pattern.matches(namespace)))
+        })
+    }
+}
+
+// The whitespace separated fields of a line, spaces being allowed inside double quotes
+struct Fields<'a>(&'a str);
+
+impl Iterator for Fields<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let s = self.0.trim_start();
+        if s.is_empty() {
+            return None;
+        }
+        let mut quoted = false;
+        let end = s
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+                c.is_whitespace() && !quoted
+            })
+            .map_or(s.len(), |(i, _)| i);
+        self.0 = &s[end..];
+        Some(s[..end].to_string())
+    }
+}
+
+// Options are comma separated, commas being allowed inside double quotes
+fn split_options(options: &str) -> Vec<&str> {
+    let mut quoted = false;
+    options
+        .split(|c| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ',' && !quoted
+        })
+        .collect()
+}
+
+fn is_key_type(field: &str) -> bool {
+    ["ssh-", "ecdsa-", "sk-"]
+        .iter()
+        .any(|prefix| field.starts_with(prefix))
+}
+
+// The data signed by ssh-keygen -Y sign, as described in OpenSSH's PROTOCOL.sshsig
+fn signed_data(namespace: &str, sig: &SshSig, hash: &[u8]) -> Vec<u8> {
+    let mut data = b"SSHSIG".to_vec();
+    for field in [
+        namespace.as_bytes(),
+        sig.reserved(),
+        sig.hash_alg().as_str().as_bytes(),
+        hash,
+    ] {
+        data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        data.extend_from_slice(field);
+    }
+    data
+}
+
+// A minisign public key, given as its base64 encoding or as the content of a minisign.pub
+// file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinisignKey {
+    key: minisign_verify::PublicKey,
+    // The base64 encoding, identifying the signer
+    encoded: String,
+}
+
+impl std::str::FromStr for MinisignKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // The key is on the last line of minisign.pub files, after the untrusted comment
+        let encoded = s.trim().lines().last().unwrap_or_default().trim();
+        let key = minisign_verify::PublicKey::from_base64(encoded)
+            .map_err(|e| Error::InvalidSignature(format!("minisign key {}: {}", encoded, e)))?;
+        Ok(Self {
+            key,
+            encoded: encoded.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for MinisignKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.encoded)
+    }
+}
+
+impl MinisignKey {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    // Starts the verification of a file signed with this key, whose content must then be
+    // written to the returned verification. Legacy signatures, which sign the content
+    // itself rather than its hash, are rejected as they cannot be verified as a stream.
+    pub fn verification<'a>(
+        &'a self,
+        signature: &'a MinisignSignature,
+    ) -> Result<MinisignVerification<'a>> {
+        let verifier = self.key.verify_stream(&signature.0).map_err(|e| match e {
+            minisign_verify::Error::UnsupportedLegacyMode => Error::InvalidSignature(
+                "legacy minisign signatures are not supported, sign the file without -l"
+                    .to_string(),
+            ),
+            e => Error::SignatureVerificationFailed(e.to_string()),
+        })?;
+        Ok(MinisignVerification {
+            signature,
+            verifier: Box::new(verifier),
+        })
+    }
+}
+
+// A signature generated by minisign
+#[derive(Clone)]
+pub struct MinisignSignature(minisign_verify::Signature);
+
+impl std::str::FromStr for MinisignSignature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        minisign_verify::Signature::decode(s.trim())
+            .map(MinisignSignature)
+            .map_err(|e| Error::InvalidSignature(e.to_string()))
+    }
+}
+
+impl MinisignSignature {
+    // The comment covered by the signature, eg timestamp:1555779966\tfile:asfald\thashed
+    pub fn trusted_comment(&self) -> &str {
+        self.0.trusted_comment()
+    }
+
+    // The file name recorded by minisign in the trusted comment, if any
+    pub fn file_name(&self) -> Option<&str> {
+        self.trusted_comment()
+            .split('\t')
+            .find_map(|field| field.strip_prefix("file:"))
+    }
+}
+
+// The verification of a minisign signature, fed with the content of the signed file
+pub struct MinisignVerification<'a> {
+    signature: &'a MinisignSignature,
+    verifier: Box<minisign_verify::StreamVerifier<'a>>,
+}
+
+impl Write for MinisignVerification<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.verifier.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MinisignVerification<'_> {
+    // Checks the signature of the content written, and that the trusted comment, which is
+    // covered by the signature, doesn't name another file than file_name. This prevents
+    // passing off the signed file for another one signed with the same key, eg an older
+    // version.
+    pub fn finish(mut self, file_name: &str) -> Result<()> {
+        self.verifier
+            .finalize()
+            .map_err(|e| Error::SignatureVerificationFailed(e.to_string()))?;
+        match self.signature.file_name() {
+            Some(signed) if signed != file_name => Err(Error::SignatureVerificationFailed(
+                format!("the signature is for {}, not {}", signed, file_name),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,1201 @@
+use crate::{hasher::HashAlgorithm, Error, Result};
+use base64ct::{Base64, Encoding};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::{collections::HashMap, path::Path};
+use x509_cert::{
+    der::{asn1::ObjectIdentifier, Decode, DecodePem, Encode},
+    ext::pkix::{name::GeneralName, BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAltName},
+    spki::SubjectPublicKeyInfoOwned,
+    Certificate,
+};
+
+// Extensions of the Sigstore bundles published next to assets, eg by cosign sign-blob
+// --bundle
+pub const SIGSTORE_BUNDLE_EXTENSIONS: [&str; 3] = [".sigstore.json", ".sigstore", ".bundle"];
+
+const EC_P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const EC_P384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+const ECDSA_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const ECDSA_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+const KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.15");
+const SUBJECT_ALT_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.17");
+const BASIC_CONSTRAINTS: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.19");
+const EXTENDED_KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.37");
+const CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+// The OIDC issuer recorded by Fulcio, as raw bytes in the legacy extension and as a DER
+// UTF8String in the current one
+const FULCIO_ISSUER_LEGACY: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+const FULCIO_ISSUER: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+// Claims of Fulcio certificates issued to CI workflows, as DER UTF8Strings
+const FULCIO_BUILD_SIGNER_URI: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.9");
+const FULCIO_SOURCE_REPOSITORY_URI: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.12");
+const FULCIO_SOURCE_REPOSITORY_REF: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.14");
+const FULCIO_BUILD_TRIGGER: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.18");
+
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+// The keys trusted to verify Sigstore bundles without network access: the certificates
+// of the Fulcio CAs, and the keys of the Rekor transparency logs. It is read from either
+// a trusted_root.json file, as distributed by Sigstore's TUF repository, or a PEM file
+// holding the CA certificates and the Rekor public key.
+#[derive(Debug, Clone)]
+pub struct SigstoreTrustRoot {
+    authorities: Vec<CertificateAuthority>,
+    logs: Vec<TransparencyLog>,
+}
+
+#[derive(Debug, Clone)]
+struct CertificateAuthority {
+    // The chain of the CA, from which signing certificates must chain up to its root
+    certificates: Vec<Certificate>,
+    valid_for: ValidFor,
+}
+
+#[derive(Debug, Clone)]
+struct TransparencyLog {
+    key: PublicKey,
+    // SHA-256 of the DER encoding of the key, identifying the log in bundles
+    key_id: Vec<u8>,
+    valid_for: ValidFor,
+}
+
+// Period, in seconds since the epoch, during which a CA or a log key may be used
+#[derive(Debug, Clone, Copy, Default)]
+struct ValidFor {
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+enum PublicKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+}
+
+// The identity a signing certificate must have been issued to, as regular expressions
+// matched against the certificate's subject alternative name (eg an email or a GitHub
+// workflow URL) and against the OIDC issuer which authenticated it. As with cosign's
+// --certificate-identity-regexp, the expressions are not anchored.
+#[derive(Debug, Clone)]
+pub struct SigstoreIdentity {
+    pub identity: Regex,
+    pub issuer: Regex,
+}
+
+// A Sigstore bundle of an asset, signed with a short-lived Fulcio certificate and logged
+// in Rekor
+#[derive(Debug, Clone)]
+pub struct SigstoreBundle {
+    certificate: Certificate,
+    tlog_entries: Vec<TlogEntry>,
+    content: BundleContent,
+}
+
+#[derive(Debug, Clone)]
+enum BundleContent {
+    // A signature of the file, with the digest signed if the bundle gives it
+    MessageSignature {
+        digest: Option<(HashAlgorithm, Vec<u8>)>,
+        signature: Vec<u8>,
+    },
+    // An in-toto statement about the file, eg its build provenance
+    DsseEnvelope(DsseEnvelope),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DsseEnvelope {
+    payload: String,
+    payload_type: String,
+    signatures: Vec<DsseSignature>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DsseSignature {
+    sig: String,
+}
+
+// An in-toto statement, see https://github.com/in-toto/attestation
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InTotoStatement {
+    #[serde(rename = "_type")]
+    pub statement_type: String,
+    pub subject: Vec<InTotoSubject>,
+    // Eg https://slsa.dev/provenance/v1
+    pub predicate_type: String,
+    #[serde(default)]
+    pub predicate: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct InTotoSubject {
+    #[serde(default)]
+    pub name: String,
+    // Hex encoded digests by algorithm, eg sha256
+    pub digest: HashMap<String, String>,
+}
+
+// What a verified bundle tells about the signer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigstoreSigner {
+    // The subject alternative name of the certificate
+    pub identity: String,
+    pub issuer: String,
+    pub log_index: u64,
+    // When the signature was logged, in seconds since the epoch
+    pub integrated_time: u64,
+}
+
+// What the certificate of a verified bundle tells about the CI build which signed, eg the
+// GitHub Actions workflow run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildClaims {
+    // The workflow which signed, eg
+    // https://github.com/owner/repo/.github/workflows/release.yml@refs/tags/v1.0.0
+    pub build_signer_uri: Option<String>,
+    // The repository the build was run for, eg https://github.com/owner/repo
+    pub source_repository_uri: Option<String>,
+    // Eg refs/tags/v1.0.0
+    pub source_repository_ref: Option<String>,
+    // The event which triggered the build, eg push
+    pub build_trigger: Option<String>,
+}
+
+// A verified bundle, with what it tells about the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedBundle {
+    pub signer: SigstoreSigner,
+    pub build: BuildClaims,
+    // The statement about the file, for bundles holding an in-toto statement rather than a
+    // signature of the file
+    pub statement: Option<InTotoStatement>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TlogEntry {
+    #[serde(deserialize_with = "int64")]
+    log_index: u64,
+    log_id: LogId,
+    kind_version: KindVersion,
+    #[serde(deserialize_with = "int64")]
+    integrated_time: u64,
+    inclusion_promise: Option<InclusionPromise>,
+    inclusion_proof: Option<InclusionProof>,
+    canonicalized_body: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogId {
+    key_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct KindVersion {
+    kind: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InclusionPromise {
+    signed_entry_timestamp: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InclusionProof {
+    #[serde(deserialize_with = "int64")]
+    log_index: u64,
+    root_hash: String,
+    #[serde(deserialize_with = "int64")]
+    tree_size: u64,
+    hashes: Vec<String>,
+    checkpoint: Checkpoint,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Checkpoint {
+    envelope: String,
+}
+
+// The JSON encoding of bundles, see sigstore's protobuf-specs
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleJson {
+    verification_material: VerificationMaterialJson,
+    message_signature: Option<MessageSignatureJson>,
+    dsse_envelope: Option<DsseEnvelope>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationMaterialJson {
+    // Bundles since v0.3 have a single certificate, older ones a chain starting with it
+    certificate: Option<RawBytes>,
+    x509_certificate_chain: Option<CertificateChainJson>,
+    #[serde(default)]
+    tlog_entries: Vec<TlogEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageSignatureJson {
+    message_digest: Option<MessageDigestJson>,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct MessageDigestJson {
+    algorithm: String,
+    digest: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBytes {
+    raw_bytes: String,
+}
+
+#[derive(Deserialize)]
+struct CertificateChainJson {
+    certificates: Vec<RawBytes>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrustedRootJson {
+    #[serde(default)]
+    tlogs: Vec<TlogJson>,
+    #[serde(default)]
+    certificate_authorities: Vec<CertificateAuthorityJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TlogJson {
+    public_key: PublicKeyJson,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublicKeyJson {
+    raw_bytes: String,
+    valid_for: Option<ValidForJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CertificateAuthorityJson {
+    cert_chain: CertificateChainJson,
+    valid_for: Option<ValidForJson>,
+}
+
+#[derive(Deserialize)]
+struct ValidForJson {
+    start: Option<String>,
+    end: Option<String>,
+}
+
+// The body of hashedrekord log entries, the type of entry cosign sign-blob creates
+#[derive(Deserialize)]
+struct HashedRekord {
+    kind: String,
+    spec: HashedRekordSpec,
+}
+
+#[derive(Deserialize)]
+struct HashedRekordSpec {
+    data: HashedRekordData,
+    signature: HashedRekordSignature,
+}
+
+#[derive(Deserialize)]
+struct HashedRekordData {
+    hash: HashedRekordHash,
+}
+
+#[derive(Deserialize)]
+struct HashedRekordHash {
+    algorithm: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HashedRekordSignature {
+    content: String,
+    public_key: HashedRekordPublicKey,
+}
+
+#[derive(Deserialize)]
+struct HashedRekordPublicKey {
+    // The base64 encoding of the PEM encoded certificate
+    content: String,
+}
+
+// The body of dsse log entries, the type of entry of DSSE bundles
+#[derive(Deserialize)]
+struct DsseRekord {
+    kind: String,
+    spec: DsseRekordSpec,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DsseRekordSpec {
+    payload_hash: HashedRekordHash,
+    signatures: Vec<DsseRekordSignature>,
+}
+
+#[derive(Deserialize)]
+struct DsseRekordSignature {
+    signature: String,
+    // The base64 encoding of the PEM encoded certificate
+    verifier: String,
+}
+
+impl std::str::FromStr for SigstoreTrustRoot {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trust_root = if s.trim_start().starts_with('{') {
+            Self::from_trusted_root_json(s)
+        } else {
+            Self::from_pem(s)
+        }
+        .map_err(Error::TrustRootError)?;
+        if trust_root.authorities.is_empty() {
+            return Err(Error::TrustRootError("no CA certificate found".to_string()));
+        }
+        if trust_root.logs.is_empty() {
+            return Err(Error::TrustRootError(
+                "no transparency log key found".to_string(),
+            ));
+        }
+        Ok(trust_root)
+    }
+}
+
+impl SigstoreTrustRoot {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    fn from_trusted_root_json(s: &str) -> std::result::Result<Self, String> {
+        let json: TrustedRootJson = serde_json::from_str(s).map_err(|e| e.to_string())?;
+        let authorities = json
+            .certificate_authorities
+            .iter()
+            .map(|ca| {
+                Ok(CertificateAuthority {
+                    certificates: ca
+                        .cert_chain
+                        .certificates
+                        .iter()
+                        .map(|c| {
+                            Certificate::from_der(&decode_base64(&c.raw_bytes)?)
+                                .map_err(|e| e.to_string())
+                        })
+                        .collect::<std::result::Result<_, _>>()?,
+                    valid_for: ValidFor::parse(ca.valid_for.as_ref())?,
+                })
+            })
+            .collect::<std::result::Result<_, String>>()?;
+        let logs = json
+            .tlogs
+            .iter()
+            .filter_map(|log| {
+                let der = Base64::decode_vec(&log.public_key.raw_bytes).ok()?;
+                // Logs using other kinds of keys cannot be used
+                let key = PublicKey::from_der(&der).ok()?;
+                Some(
+                    ValidFor::parse(log.public_key.valid_for.as_ref()).map(|valid_for| {
+                        TransparencyLog {
+                            key,
+                            key_id: Sha256::digest(&der).to_vec(),
+                            valid_for,
+                        }
+                    }),
+                )
+            })
+            .collect::<std::result::Result<_, String>>()?;
+        Ok(Self { authorities, logs })
+    }
+
+    // The CA certificates form a single authority, the public keys are those of logs
+    fn from_pem(s: &str) -> std::result::Result<Self, String> {
+        let mut certificates = vec![];
+        let mut logs = vec![];
+        for (label, der) in pem_blocks(s)? {
+            match label.as_str() {
+                "CERTIFICATE" => {
+                    certificates.push(Certificate::from_der(&der).map_err(|e| e.to_string())?)
+                }
+                "PUBLIC KEY" => logs.push(TransparencyLog {
+                    key: PublicKey::from_der(&der)?,
+                    key_id: Sha256::digest(&der).to_vec(),
+                    valid_for: ValidFor::default(),
+                }),
+                _ => return Err(format!("unexpected PEM block {}", label)),
+            }
+        }
+        let authorities = if certificates.is_empty() {
+            vec![]
+        } else {
+            vec![CertificateAuthority {
+                certificates,
+                valid_for: ValidFor::default(),
+            }]
+        };
+        Ok(Self { authorities, logs })
+    }
+
+    // Checks that the certificate was issued by a trusted CA, and that the certificates
+    // of the chain were all valid at the time given
+    fn verify_certificate(
+        &self,
+        certificate: &Certificate,
+        time: u64,
+    ) -> std::result::Result<(), String> {
+        if !certificate_valid_at(certificate, time) {
+            return Err(format!(
+                "the certificate was not valid when the signature was logged, on {}",
+                format_time(time)
+            ));
+        }
+        if extension::<BasicConstraints>(certificate, BASIC_CONSTRAINTS)?.is_some_and(|c| c.ca) {
+            return Err("the signing certificate is a CA certificate".to_string());
+        }
+        let issued = self
+            .authorities
+            .iter()
+            .filter(|ca| ca.valid_for.contains(time))
+            .any(|ca| ca.verify_path(certificate, time).is_ok());
+        if !issued {
+            return Err("the certificate was not issued by a trusted CA".to_string());
+        }
+        Ok(())
+    }
+
+    fn log(&self, key_id: &[u8], time: u64) -> Option<&TransparencyLog> {
+        self.logs
+            .iter()
+            .find(|log| log.key_id == key_id && log.valid_for.contains(time))
+    }
+}
+
+impl CertificateAuthority {
+    // Checks that the certificate chains up to the root of the CA, each certificate being
+    // issued by a certificate of the CA allowed to issue it at the time given
+    fn verify_path(&self, certificate: &Certificate, time: u64) -> std::result::Result<(), String> {
+        let mut current = certificate;
+        // Each certificate of the CA appears at most once in the path
+        for intermediates in 0..self.certificates.len() {
+            let issuer = self
+                .certificates
+                .iter()
+                .filter(|ca| certificate_valid_at(ca, time))
+                .filter(|ca| may_issue(ca, intermediates).is_ok())
+                .find(|ca| verify_issued(current, ca).is_ok())
+                .ok_or("no issuer found")?;
+            // The root of the CA is self-signed
+            if issuer.tbs_certificate.issuer == issuer.tbs_certificate.subject {
+                return Ok(());
+            }
+            current = issuer;
+        }
+        Err("the certificate chain does not end with a root".to_string())
+    }
+}
+
+impl ValidFor {
+    fn parse(json: Option<&ValidForJson>) -> std::result::Result<Self, String> {
+        let parse = |time: Option<&String>| {
+            time.map(|t| parse_time(t).ok_or_else(|| format!("invalid time {}", t)))
+                .transpose()
+        };
+        Ok(Self {
+            start: parse(json.and_then(|j| j.start.as_ref()))?,
+            end: parse(json.and_then(|j| j.end.as_ref()))?,
+        })
+    }
+
+    fn contains(&self, time: u64) -> bool {
+        self.start.is_none_or(|start| start <= time) && self.end.is_none_or(|end| time <= end)
+    }
+}
+
+impl PublicKey {
+    fn from_der(der: &[u8]) -> std::result::Result<Self, String> {
+        let spki = SubjectPublicKeyInfoOwned::from_der(der).map_err(|e| e.to_string())?;
+        Self::from_spki(&spki)
+    }
+
+    fn from_spki(spki: &SubjectPublicKeyInfoOwned) -> std::result::Result<Self, String> {
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|p| p.decode_as::<ObjectIdentifier>().ok());
+        let point = spki.subject_public_key.raw_bytes();
+        match curve {
+            Some(EC_P256) => p256::ecdsa::VerifyingKey::from_sec1_bytes(point)
+                .map(PublicKey::P256)
+                .map_err(|e| e.to_string()),
+            Some(EC_P384) => p384::ecdsa::VerifyingKey::from_sec1_bytes(point)
+                .map(PublicKey::P384)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!(
+                "unsupported public key algorithm {}",
+                spki.algorithm.oid
+            )),
+        }
+    }
+
+    // Verifies a DER encoded signature of message, hashed as usual for the curve
+    fn verify(&self, message: &[u8], signature: &[u8]) -> std::result::Result<(), String> {
+        match self {
+            PublicKey::P256(_) => self.verify_prehash(&Sha256::digest(message), signature),
+            PublicKey::P384(_) => self.verify_prehash(&Sha384::digest(message), signature),
+        }
+    }
+
+    // Verifies a DER encoded signature of a digest
+    fn verify_prehash(&self, digest: &[u8], signature: &[u8]) -> std::result::Result<(), String> {
+        let failed = |e: p256::ecdsa::Error| e.to_string();
+        match self {
+            PublicKey::P256(key) => {
+                use p256::ecdsa::signature::hazmat::PrehashVerifier;
+                let signature = p256::ecdsa::Signature::from_der(signature).map_err(failed)?;
+                key.verify_prehash(digest, &signature).map_err(failed)
+            }
+            PublicKey::P384(key) => {
+                use p384::ecdsa::signature::hazmat::PrehashVerifier;
+                let signature = p384::ecdsa::Signature::from_der(signature).map_err(failed)?;
+                key.verify_prehash(digest, &signature).map_err(failed)
+            }
+        }
+    }
+}
+
+impl SigstoreIdentity {
+    pub fn new(identity: &str, issuer: &str) -> Result<Self> {
+        let regex = |r: &str| {
+            Regex::new(r)
+                .map_err(|e| Error::InvalidPolicy(format!("invalid regular expression: {}", e)))
+        };
+        Ok(Self {
+            identity: regex(identity)?,
+            issuer: regex(issuer)?,
+        })
+    }
+}
+
+impl PartialEq for SigstoreIdentity {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity.as_str() == other.identity.as_str()
+            && self.issuer.as_str() == other.issuer.as_str()
+    }
+}
+
+impl Eq for SigstoreIdentity {}
+
+impl std::str::FromStr for SigstoreBundle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let json: BundleJson =
+            serde_json::from_str(s).map_err(|e| Error::InvalidSignature(e.to_string()))?;
+        let material = json.verification_material;
+        let certificate = match (&material.certificate, &material.x509_certificate_chain) {
+            (Some(certificate), _) => certificate,
+            (None, Some(chain)) if !chain.certificates.is_empty() => &chain.certificates[0],
+            _ => {
+                return Err(Error::InvalidSignature(
+                    "only bundles signed with a certificate are supported".to_string(),
+                ))
+            }
+        };
+        let certificate = Certificate::from_der(
+            &decode_base64(&certificate.raw_bytes).map_err(Error::InvalidSignature)?,
+        )
+        .map_err(|e| Error::InvalidSignature(e.to_string()))?;
+        let content = match (json.message_signature, json.dsse_envelope) {
+            (Some(message_signature), None) => BundleContent::MessageSignature {
+                digest: message_signature
+                    .message_digest
+                    .map(|digest| {
+                        Ok((
+                            bundle_hash_algorithm(&digest.algorithm)?,
+                            decode_base64(&digest.digest)?,
+                        ))
+                    })
+                    .transpose()
+                    .map_err(Error::InvalidSignature)?,
+                signature: decode_base64(&message_signature.signature)
+                    .map_err(Error::InvalidSignature)?,
+            },
+            (None, Some(envelope)) => BundleContent::DsseEnvelope(envelope),
+            _ => {
+                return Err(Error::InvalidSignature(
+                    "the bundle must hold either a signature or a DSSE envelope".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            certificate,
+            tlog_entries: material.tlog_entries,
+            content,
+        })
+    }
+}
+
+impl SigstoreBundle {
+    // The algorithm of the digest signed, sha256 unless the bundle tells otherwise
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        match &self.content {
+            BundleContent::MessageSignature {
+                digest: Some((algorithm, _)),
+                ..
+            } => algorithm.clone(),
+            _ => HashAlgorithm::Sha256,
+        }
+    }
+
+    // Verifies the bundle of a file from its digests, which must include one computed with
+    // the bundle's hash algorithm:
+    // - the signature was logged in a trusted Rekor log, as proven by the log's signed
+    //   promise, which also covers the time it was logged, and by the inclusion proof in a
+    //   signed checkpoint if there is one
+    // - the certificate was issued by a trusted Fulcio CA for code signing, and was valid
+    //   when the signature was logged
+    // - the signature of the digest, or of the in-toto statement about the file, is valid
+    // The identity of the certificate is returned but not checked.
+    pub fn verify(
+        &self,
+        trust_root: &SigstoreTrustRoot,
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<VerifiedBundle> {
+        let failed = Error::SignatureVerificationFailed;
+        let entry = self.verify_tlog_entry(trust_root).map_err(failed)?;
+        trust_root
+            .verify_certificate(&self.certificate, entry.integrated_time)
+            .map_err(failed)?;
+        let extended_key_usage = self
+            .extension::<ExtendedKeyUsage>(EXTENDED_KEY_USAGE)
+            .map_err(failed)?;
+        if !extended_key_usage.is_some_and(|usage| usage.0.contains(&CODE_SIGNING)) {
+            return Err(failed(
+                "the certificate is not issued for code signing".to_string(),
+            ));
+        }
+        let key = PublicKey::from_spki(&self.certificate.tbs_certificate.subject_public_key_info)
+            .map_err(failed)?;
+
+        let statement = match &self.content {
+            BundleContent::MessageSignature {
+                digest: signed,
+                signature,
+            } => {
+                let algorithm = self.hash_algorithm();
+                let (_, digest) = digests
+                    .iter()
+                    .find(|(a, _)| *a == algorithm)
+                    .ok_or_else(|| failed(format!("no {} digest computed", algorithm)))?;
+                let digest = hex::decode(digest).map_err(|e| Error::HashError(e.to_string()))?;
+                if signed.as_ref().is_some_and(|(_, signed)| *signed != digest) {
+                    return Err(failed("the bundle is for another file".to_string()));
+                }
+                self.verify_hashed_rekord(entry, &algorithm, &digest, signature)
+                    .map_err(failed)?;
+                key.verify_prehash(&digest, signature)
+                    .map_err(|e| failed(format!("invalid signature: {}", e)))?;
+                None
+            }
+            BundleContent::DsseEnvelope(envelope) => {
+                let (statement, signature) = envelope.verify(&key, digests).map_err(failed)?;
+                self.verify_dsse_rekord(entry, envelope, &signature)
+                    .map_err(failed)?;
+                Some(statement)
+            }
+        };
+
+        Ok(VerifiedBundle {
+            signer: SigstoreSigner {
+                identity: self.identity().map_err(failed)?,
+                issuer: self.issuer().map_err(failed)?,
+                log_index: entry.log_index,
+                integrated_time: entry.integrated_time,
+            },
+            build: self.build_claims().map_err(failed)?,
+            statement,
+        })
+    }
+
+    // Verifies the bundle like verify, and checks the certificate's identity
+    pub fn verify_digest(
+        &self,
+        trust_root: &SigstoreTrustRoot,
+        identity: &SigstoreIdentity,
+        digests: &[(HashAlgorithm, String)],
+    ) -> Result<SigstoreSigner> {
+        let signer = self.verify(trust_root, digests)?.signer;
+        if !identity.identity.is_match(&signer.identity) {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "the certificate identity {} doesn't match {}",
+                signer.identity, identity.identity
+            )));
+        }
+        if !identity.issuer.is_match(&signer.issuer) {
+            return Err(Error::SignatureVerificationFailed(format!(
+                "the certificate issuer {} doesn't match {}",
+                signer.issuer, identity.issuer
+            )));
+        }
+        Ok(signer)
+    }
+
+    // Finds the entry of a trusted log, and checks it was included in the log
+    fn verify_tlog_entry(
+        &self,
+        trust_root: &SigstoreTrustRoot,
+    ) -> std::result::Result<&TlogEntry, String> {
+        let (entry, log) = self
+            .tlog_entries
+            .iter()
+            .find_map(|entry| {
+                let key_id = Base64::decode_vec(&entry.log_id.key_id).ok()?;
+                Some((entry, trust_root.log(&key_id, entry.integrated_time)?))
+            })
+            .ok_or("the signature was not logged in a trusted transparency log")?;
+
+        // The integration time, against which the certificate is checked, is only signed by
+        // the promise. Timestamps from timestamping authorities are not supported.
+        let promise = entry.inclusion_promise.as_ref().ok_or(
+            "the log entry has no inclusion promise, the time it was logged cannot be trusted",
+        )?;
+        // The canonical JSON of the entry, as signed by the log
+        let payload = format!(
+            r#"{{"body":"{}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+            entry.canonicalized_body,
+            entry.integrated_time,
+            hex::encode(&log.key_id),
+            entry.log_index
+        );
+        log.key
+            .verify(
+                payload.as_bytes(),
+                &decode_base64(&promise.signed_entry_timestamp)?,
+            )
+            .map_err(|_| "invalid signed entry timestamp".to_string())?;
+        if let Some(proof) = &entry.inclusion_proof {
+            let body = decode_base64(&entry.canonicalized_body)?;
+            verify_inclusion_proof(proof, &body, log)?;
+        }
+        Ok(entry)
+    }
+
+    // Checks that the log entry is the one of this bundle's signature of digest
+    fn verify_hashed_rekord(
+        &self,
+        entry: &TlogEntry,
+        algorithm: &HashAlgorithm,
+        digest: &[u8],
+        signature: &[u8],
+    ) -> std::result::Result<(), String> {
+        let body: HashedRekord = serde_json::from_slice(&decode_base64(&entry.canonicalized_body)?)
+            .map_err(|e| format!("invalid log entry: {}", e))?;
+        if body.kind != "hashedrekord" || entry.kind_version.kind != body.kind {
+            return Err(format!("unsupported log entry kind {}", body.kind));
+        }
+        let hash = &body.spec.data.hash;
+        if hash.algorithm != algorithm.to_string()
+            || hex::decode(&hash.value).ok().as_deref() != Some(digest)
+        {
+            return Err("the log entry is for another file".to_string());
+        }
+        if decode_base64(&body.spec.signature.content)? != signature {
+            return Err("the log entry is for another signature".to_string());
+        }
+        if !self.is_certificate(&body.spec.signature.public_key.content)? {
+            return Err("the log entry is for another certificate".to_string());
+        }
+        Ok(())
+    }
+
+    // Checks that the log entry is the one of this bundle's envelope, with the signature
+    // verified
+    fn verify_dsse_rekord(
+        &self,
+        entry: &TlogEntry,
+        envelope: &DsseEnvelope,
+        signature: &[u8],
+    ) -> std::result::Result<(), String> {
+        let body: DsseRekord = serde_json::from_slice(&decode_base64(&entry.canonicalized_body)?)
+            .map_err(|e| format!("invalid log entry: {}", e))?;
+        if body.kind != "dsse" || entry.kind_version.kind != body.kind {
+            return Err(format!("unsupported log entry kind {}", body.kind));
+        }
+        let payload_hash = &body.spec.payload_hash;
+        if payload_hash.algorithm != "sha256"
+            || hex::decode(&payload_hash.value).ok()
+                != Some(Sha256::digest(decode_base64(&envelope.payload)?).to_vec())
+        {
+            return Err("the log entry is for another statement".to_string());
+        }
+        for logged in &body.spec.signatures {
+            if decode_base64(&logged.signature)? == signature
+                && self.is_certificate(&logged.verifier)?
+            {
+                return Ok(());
+            }
+        }
+        Err("the log entry is for another signature or certificate".to_string())
+    }
+
+    // Whether pem, base64 encoded as in log entries, is the bundle's certificate
+    fn is_certificate(&self, pem: &str) -> std::result::Result<bool, String> {
+        let certificate = Certificate::from_pem(decode_base64(pem)?).map_err(|e| e.to_string())?;
+        Ok(certificate == self.certificate)
+    }
+
+    fn extension<'a, T: Decode<'a>>(
+        &'a self,
+        oid: ObjectIdentifier,
+    ) -> std::result::Result<Option<T>, String> {
+        extension(&self.certificate, oid)
+    }
+
+    // The email or URI the certificate was issued for
+    fn identity(&self) -> std::result::Result<String, String> {
+        let names = self
+            .extension::<SubjectAltName>(SUBJECT_ALT_NAME)?
+            .ok_or("the certificate has no subject alternative name")?;
+        names
+            .0
+            .iter()
+            .find_map(|name| match name {
+                GeneralName::Rfc822Name(email) => Some(email.to_string()),
+                GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| "the certificate has no email or URI identity".to_string())
+    }
+
+    fn issuer(&self) -> std::result::Result<String, String> {
+        if let Some(issuer) =
+            self.extension::<x509_cert::der::asn1::Utf8StringRef>(FULCIO_ISSUER)?
+        {
+            return Ok(issuer.to_string());
+        }
+        self.certificate
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .find(|extension| extension.extn_id == FULCIO_ISSUER_LEGACY)
+            .and_then(|extension| std::str::from_utf8(extension.extn_value.as_bytes()).ok())
+            .map(str::to_string)
+            .ok_or_else(|| "the certificate has no OIDC issuer".to_string())
+    }
+
+    fn build_claims(&self) -> std::result::Result<BuildClaims, String> {
+        let claim = |oid| {
+            self.extension::<x509_cert::der::asn1::Utf8StringRef>(oid)
+                .map(|claim| claim.map(|claim| claim.to_string()))
+        };
+        Ok(BuildClaims {
+            build_signer_uri: claim(FULCIO_BUILD_SIGNER_URI)?,
+            source_repository_uri: claim(FULCIO_SOURCE_REPOSITORY_URI)?,
+            source_repository_ref: claim(FULCIO_SOURCE_REPOSITORY_REF)?,
+            build_trigger: claim(FULCIO_BUILD_TRIGGER)?,
+        })
+    }
+}
+
+impl DsseEnvelope {
+    // Checks that the envelope holds an in-toto statement about a file with one of the
+    // digests, signed with key. The statement is returned with the valid signature.
+    fn verify(
+        &self,
+        key: &PublicKey,
+        digests: &[(HashAlgorithm, String)],
+    ) -> std::result::Result<(InTotoStatement, Vec<u8>), String> {
+        if self.payload_type != IN_TOTO_PAYLOAD_TYPE {
+            return Err(format!("unsupported payload type {}", self.payload_type));
+        }
+        let payload = decode_base64(&self.payload)?;
+        // The pre-authentication encoding of the payload, see
+        // https://github.com/secure-systems-lab/dsse/blob/master/protocol.md
+        let mut signed = format!(
+            "DSSEv1 {} {} {} ",
+            self.payload_type.len(),
+            self.payload_type,
+            payload.len()
+        )
+        .into_bytes();
+        signed.extend(&payload);
+        let signature = self
+            .signatures
+            .iter()
+            .filter_map(|signature| decode_base64(&signature.sig).ok())
+            .find(|signature| key.verify(&signed, signature).is_ok())
+            .ok_or("invalid signature")?;
+
+        let statement: InTotoStatement = serde_json::from_slice(&payload)
+            .map_err(|e| format!("invalid in-toto statement: {}", e))?;
+        if !statement
+            .statement_type
+            .starts_with("https://in-toto.io/Statement/")
+        {
+            return Err(format!(
+                "unsupported statement type {}",
+                statement.statement_type
+            ));
+        }
+        let about_file = statement.subject.iter().any(|subject| {
+            digests.iter().any(|(algorithm, digest)| {
+                subject
+                    .digest
+                    .get(&algorithm.to_string())
+                    .is_some_and(|d| d.eq_ignore_ascii_case(digest))
+            })
+        });
+        if !about_file {
+            return Err("the statement is about another file".to_string());
+        }
+        Ok((statement, signature))
+    }
+}
+
+// Checks that certificate was signed by issuer
+fn verify_issued(
+    certificate: &Certificate,
+    issuer: &Certificate,
+) -> std::result::Result<(), String> {
+    if certificate.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return Err("issuer mismatch".to_string());
+    }
+    let tbs = certificate
+        .tbs_certificate
+        .to_der()
+        .map_err(|e| e.to_string())?;
+    let digest = match certificate.signature_algorithm.oid {
+        ECDSA_SHA256 => Sha256::digest(&tbs).to_vec(),
+        ECDSA_SHA384 => Sha384::digest(&tbs).to_vec(),
+        ECDSA_SHA512 => Sha512::digest(&tbs).to_vec(),
+        oid => return Err(format!("unsupported signature algorithm {}", oid)),
+    };
+    let signature = certificate
+        .signature
+        .as_bytes()
+        .ok_or("invalid certificate signature")?;
+    PublicKey::from_spki(&issuer.tbs_certificate.subject_public_key_info)?
+        .verify_prehash(&digest, signature)
+}
+
+// Checks that issuer is a CA certificate which may sign certificates, with the given
+// number of intermediate CA certificates below it in the path, see RFC 5280 section 4.2.1
+fn may_issue(issuer: &Certificate, intermediates: usize) -> std::result::Result<(), String> {
+    let constraints = extension::<BasicConstraints>(issuer, BASIC_CONSTRAINTS)?
+        .filter(|constraints| constraints.ca)
+        .ok_or("the issuer is not a CA certificate")?;
+    if constraints
+        .path_len_constraint
+        .is_some_and(|path_len| usize::from(path_len) < intermediates)
+    {
+        return Err("the certificate path is longer than allowed by the issuer".to_string());
+    }
+    if !extension::<KeyUsage>(issuer, KEY_USAGE)?.is_some_and(|usage| usage.key_cert_sign()) {
+        return Err("the issuer is not allowed to sign certificates".to_string());
+    }
+    Ok(())
+}
+
+fn extension<'a, T: Decode<'a>>(
+    certificate: &'a Certificate,
+    oid: ObjectIdentifier,
+) -> std::result::Result<Option<T>, String> {
+    certificate
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|extension| extension.extn_id == oid)
+        .map(|extension| T::from_der(extension.extn_value.as_bytes()))
+        .transpose()
+        .map_err(|e| format!("invalid certificate extension {}: {}", oid, e))
+}
+
+fn certificate_valid_at(certificate: &Certificate, time: u64) -> bool {
+    let validity = &certificate.tbs_certificate.validity;
+    validity.not_before.to_unix_duration().as_secs() <= time
+        && time <= validity.not_after.to_unix_duration().as_secs()
+}
+
+// Checks that the entry body is included in the tree whose root hash is in the checkpoint
+// signed by the log, see RFC 9162 section 2.1.3.2
+fn verify_inclusion_proof(
+    proof: &InclusionProof,
+    body: &[u8],
+    log: &TransparencyLog,
+) -> std::result::Result<(), String> {
+    let hash = |parts: &[&[u8]]| -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().to_vec()
+    };
+    let invalid = || "invalid inclusion proof".to_string();
+    if proof.log_index >= proof.tree_size {
+        return Err(invalid());
+    }
+    let hashes = proof
+        .hashes
+        .iter()
+        .map(|h| decode_base64(h))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let (mut index, mut last) = (proof.log_index, proof.tree_size - 1);
+    let mut root = hash(&[&[0], body]);
+    for sibling in &hashes {
+        if last == 0 {
+            return Err(invalid());
+        }
+        if index % 2 == 1 || index == last {
+            root = hash(&[&[1], sibling, &root]);
+            if index % 2 == 0 {
+                while index % 2 == 0 && index != 0 {
+                    index >>= 1;
+                    last >>= 1;
+                }
+            }
+        } else {
+            root = hash(&[&[1], &root, sibling]);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+    if last != 0 || root != decode_base64(&proof.root_hash)? {
+        return Err(invalid());
+    }
+    verify_checkpoint(&proof.checkpoint.envelope, log, proof.tree_size, &root)
+}
+
+// Checks that the checkpoint, a signed note, is signed by the log and is for the tree
+// size and root hash of the proof
+fn verify_checkpoint(
+    envelope: &str,
+    log: &TransparencyLog,
+    tree_size: u64,
+    root_hash: &[u8],
+) -> std::result::Result<(), String> {
+    let invalid = || "invalid checkpoint".to_string();
+    let (note, signatures) = envelope.split_once("\n\n").ok_or_else(invalid)?;
+    let mut lines = note.lines().skip(1);
+    if lines.next().and_then(|size| size.parse().ok()) != Some(tree_size)
+        || lines
+            .next()
+            .and_then(|hash| Base64::decode_vec(hash).ok())
+            .as_deref()
+            != Some(root_hash)
+    {
+        return Err("the checkpoint is not for the inclusion proof".to_string());
+    }
+    // Signature lines are "— <name> <base64 of the key hint and signature>", the key hint
+    // being the start of the key ID
+    let signed = format!("{}\n", note);
+    let verified = signatures
+        .lines()
+        .filter_map(|line| line.strip_prefix("\u{2014} ")?.rsplit_once(' '))
+        .filter_map(|(_, signature)| Base64::decode_vec(signature).ok())
+        .any(|signature| {
+            signature.len() > 4
+                && signature[..4] == log.key_id[..4]
+                && log.key.verify(signed.as_bytes(), &signature[4..]).is_ok()
+        });
+    if !verified {
+        return Err("the checkpoint is not signed by the log".to_string());
+    }
+    Ok(())
+}
+
+fn bundle_hash_algorithm(algorithm: &str) -> std::result::Result<HashAlgorithm, String> {
+    match algorithm {
+        "SHA2_256" => Ok(HashAlgorithm::Sha256),
+        "SHA2_384" => Ok(HashAlgorithm::Sha384),
+        "SHA2_512" => Ok(HashAlgorithm::Sha512),
+        _ => Err(format!("unsupported hash algorithm {}", algorithm)),
+    }
+}
+
+fn decode_base64(s: &str) -> std::result::Result<Vec<u8>, String> {
+    Base64::decode_vec(s).map_err(|e| format!("invalid base64: {}", e))
+}
+
+// The label and content of the PEM blocks in s
+fn pem_blocks(s: &str) -> std::result::Result<Vec<(String, Vec<u8>)>, String> {
+    let mut blocks = vec![];
+    let mut lines = s.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(label) = line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|l| l.strip_suffix("-----"))
+        else {
+            continue;
+        };
+        let end = format!("-----END {}-----", label);
+        let base64: String = lines.by_ref().take_while(|line| *line != end).collect();
+        blocks.push((label.to_string(), decode_base64(&base64)?));
+    }
+    Ok(blocks)
+}
+
+// Integers are encoded as strings in the JSON encoding of protobuf messages
+fn int64<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int64 {
+        Number(u64),
+        String(String),
+    }
+    match Int64::deserialize(deserializer)? {
+        Int64::Number(n) => Ok(n),
+        Int64::String(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+// Parses an RFC 3339 time in UTC, eg 2021-01-12T11:53:27.000Z, as seconds since the epoch
+fn parse_time(time: &str) -> Option<u64> {
+    let (date, time) = time.split_once('T')?;
+    let time = time.strip_suffix('Z')?;
+    let time = time.split('.').next()?;
+    let mut date = date.split('-').map(|n| n.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.split(':').map(|n| n.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    // Days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+fn format_time(time: u64) -> String {
+    httpdate::fmt_http_date(std::time::UNIX_EPOCH + std::time::Duration::from_secs(time))
+}